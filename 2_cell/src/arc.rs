@@ -0,0 +1,294 @@
+// Rc, but the counts are atomics so clones can live on different threads.
+//
+// The protocol (same as std's):
+// - increments are Relaxed: you need an Arc (or Weak) already to make a new one, so the count can't
+//   be at 0 and nothing is being freed, there's nothing to synchronize with.
+// - decrements are Release: everything this thread did with the value happens before the count
+//   goes down...
+// - ...and whoever takes it to 0 does an Acquire fence before dropping/freeing, which syncs with all
+//   of those Releases, so every other thread is done with the value by the time it's dropped.
+// The fence is only paid for by the last one, instead of every decrement being AcqRel.
+
+#[cfg(loom)]
+use loom::sync::atomic::{fence, AtomicUsize};
+#[cfg(not(loom))]
+use std::sync::atomic::{fence, AtomicUsize};
+
+use std::marker::PhantomData;
+use std::mem::ManuallyDrop;
+use std::ptr::NonNull;
+use std::sync::atomic::Ordering::{Acquire, Relaxed, Release};
+
+// same layout as Rc's SharedValue, see there: counts first, value last
+#[repr(C)]
+struct ArcInner<T> {
+    strong: AtomicUsize,
+    weak: AtomicUsize, // one for every Weak, plus one for all the Arcs together
+    value: ManuallyDrop<T>,
+}
+
+// Never more than this many, someone leaking Arcs in a loop would otherwise overflow the count and
+// free the value under everyone else. Far beyond anything real, so just give up.
+const MAX_REFCOUNT: usize = isize::MAX as usize;
+
+// SAFETY: ptr must point at an ArcInner whose memory hasn't been freed yet
+unsafe fn counts<'a, T>(ptr: NonNull<ArcInner<T>>) -> (&'a AtomicUsize, &'a AtomicUsize) {
+    let ptr = ptr.as_ptr();
+    (&(*ptr).strong, &(*ptr).weak)
+}
+
+// SAFETY: same as counts, and the caller's weak count can't be used again after this
+unsafe fn release_weak<T>(ptr: NonNull<ArcInner<T>>) {
+    let (_, weak) = counts(ptr);
+    if weak.fetch_sub(1, Release) == 1 {
+        // syncs with the Release of every other Weak (and the Arcs) that went away before us
+        fence(Acquire);
+        let _ = Box::from_raw(ptr.as_ptr());
+    }
+}
+
+fn increment(count: &AtomicUsize) {
+    if count.fetch_add(1, Relaxed) > MAX_REFCOUNT {
+        std::process::abort();
+    }
+}
+
+pub struct Arc<T> {
+    inner: NonNull<ArcInner<T>>,
+    _marker: PhantomData<ArcInner<T>>, // fix for drop check
+}
+
+pub struct Weak<T> {
+    inner: NonNull<ArcInner<T>>,
+}
+
+// Sync because clones on other threads give out &T at the same time. Send too, because the last Arc
+// drops the T, which could be on any thread, so sending an Arc<T> can end up sending a T.
+// A Weak can be upgraded to an Arc, so the same goes for it.
+unsafe impl<T: Send + Sync> Send for Arc<T> {}
+unsafe impl<T: Send + Sync> Sync for Arc<T> {}
+unsafe impl<T: Send + Sync> Send for Weak<T> {}
+unsafe impl<T: Send + Sync> Sync for Weak<T> {}
+
+impl<T> Arc<T> {
+    pub fn new(v: T) -> Self {
+        let inner = Box::new(ArcInner {
+            strong: AtomicUsize::new(1),
+            weak: AtomicUsize::new(1),
+            value: ManuallyDrop::new(v),
+        });
+        Arc {
+            // SAFETY: Box does not give us a null pointer
+            inner: unsafe { NonNull::new_unchecked(Box::into_raw(inner)) },
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn downgrade(this: &Self) -> Weak<T> {
+        let (_, weak) = unsafe { counts(this.inner) };
+        increment(weak);
+        Weak { inner: this.inner }
+    }
+
+    // these two can be out of date by the time you look at them if other threads have clones
+    pub fn strong_count(this: &Self) -> usize {
+        unsafe { counts(this.inner) }.0.load(Relaxed)
+    }
+
+    pub fn weak_count(this: &Self) -> usize {
+        unsafe { counts(this.inner) }.1.load(Relaxed) - 1
+    }
+
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        this.inner == other.inner
+    }
+}
+
+impl<T> Weak<T> {
+    pub fn upgrade(&self) -> Option<Arc<T>> {
+        // SAFETY: we're a Weak, so the memory is still there, even if the value may not be
+        let (strong, _) = unsafe { counts(self.inner) };
+        // Can't fetch_add like clone does: if the last Arc is going away right now, the count
+        // must stay at 0. So only ever go up from something that isn't 0.
+        let mut n = strong.load(Relaxed);
+        loop {
+            if n == 0 {
+                return None;
+            }
+            if n > MAX_REFCOUNT {
+                std::process::abort();
+            }
+            // Acquire on success to be on the safe side: what we get out of it is a &T, so we want
+            // to see everything that happened to it before the Arc we're piggybacking on was made.
+            match strong.compare_exchange_weak(n, n + 1, Acquire, Relaxed) {
+                Ok(_) => {
+                    return Some(Arc {
+                        inner: self.inner,
+                        _marker: PhantomData,
+                    })
+                }
+                Err(actual) => n = actual,
+            }
+        }
+    }
+}
+
+impl<T> Clone for Arc<T> {
+    fn clone(&self) -> Self {
+        let (strong, _) = unsafe { counts(self.inner) };
+        increment(strong);
+        Arc {
+            inner: self.inner,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Clone for Weak<T> {
+    fn clone(&self) -> Self {
+        let (_, weak) = unsafe { counts(self.inner) };
+        increment(weak);
+        Weak { inner: self.inner }
+    }
+}
+
+impl<T> std::ops::Deref for Arc<T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: we have an Arc, so the value hasn't been dropped, and only shared refs to it
+        // are ever handed out
+        &unsafe { self.inner.as_ref() }.value
+    }
+}
+
+impl<T> Drop for Arc<T> {
+    fn drop(&mut self) {
+        let (strong, _) = unsafe { counts(self.inner) };
+        if strong.fetch_sub(1, Release) != 1 {
+            return;
+        }
+        // syncs with the Release of every Arc that went away before us, so any use of the value
+        // on their threads happens before this drop
+        fence(Acquire);
+        // SAFETY: we were the last Arc, and strong is 0 so no Weak can upgrade anymore
+        unsafe {
+            ManuallyDrop::drop(&mut (*self.inner.as_ptr()).value);
+            release_weak(self.inner);
+        }
+    }
+}
+
+impl<T> Drop for Weak<T> {
+    fn drop(&mut self) {
+        // SAFETY: we're a Weak, so the memory is still there, and this is us going away
+        unsafe { release_weak(self.inner) };
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::thread;
+
+    struct Tracked<'a>(&'a AtomicUsize);
+
+    impl Drop for Tracked<'_> {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Relaxed);
+        }
+    }
+
+    #[test]
+    fn threads() {
+        let drops = AtomicUsize::new(0);
+        let a = Arc::new(Tracked(&drops));
+        let w = Arc::downgrade(&a);
+        thread::scope(|s| {
+            for _ in 0..8 {
+                let a = Arc::clone(&a);
+                let w = w.clone();
+                s.spawn(move || {
+                    for _ in 0..1000 {
+                        let b = Arc::clone(&a);
+                        let c = w.upgrade().unwrap();
+                        assert!(Arc::ptr_eq(&b, &c));
+                    }
+                });
+            }
+        });
+        assert_eq!(Arc::strong_count(&a), 1);
+        assert_eq!(Arc::weak_count(&a), 1);
+        assert_eq!(drops.load(Relaxed), 0);
+        drop(a);
+        assert_eq!(drops.load(Relaxed), 1);
+        assert!(w.upgrade().is_none());
+    }
+}
+
+// RUSTFLAGS="--cfg loom" cargo test --release arc
+// The value writes to a loom UnsafeCell when it's dropped and reads it on every use, so loom flags
+// any interleaving where the drop isn't ordered after all the uses.
+#[cfg(all(test, loom))]
+mod tests {
+    use super::*;
+    use loom::cell::UnsafeCell;
+    use loom::thread;
+
+    struct Checked(UnsafeCell<usize>);
+
+    impl Checked {
+        fn new() -> Self {
+            Checked(UnsafeCell::new(1))
+        }
+
+        fn read(&self) -> usize {
+            self.0.with(|v| unsafe { *v })
+        }
+    }
+
+    impl Drop for Checked {
+        fn drop(&mut self) {
+            self.0.with_mut(|v| unsafe { *v = 0 });
+        }
+    }
+
+    #[test]
+    fn clone_on_another_thread() {
+        loom::model(|| {
+            let a = Arc::new(Checked::new());
+            let b = Arc::clone(&a);
+            let t = thread::spawn(move || assert_eq!(b.read(), 1));
+            assert_eq!(a.read(), 1);
+            drop(a);
+            t.join().unwrap();
+        });
+    }
+
+    #[test]
+    fn upgrade_while_dropping() {
+        loom::model(|| {
+            let a = Arc::new(Checked::new());
+            let w = Arc::downgrade(&a);
+            let t = thread::spawn(move || {
+                if let Some(a) = w.upgrade() {
+                    assert_eq!(a.read(), 1);
+                }
+            });
+            drop(a);
+            t.join().unwrap();
+        });
+    }
+
+    #[test]
+    fn last_weak_and_last_arc() {
+        // whichever goes last frees the memory, exactly once
+        loom::model(|| {
+            let a = Arc::new(Checked::new());
+            let w = Arc::downgrade(&a);
+            let t = thread::spawn(move || drop(w));
+            drop(a);
+            t.join().unwrap();
+        });
+    }
+}
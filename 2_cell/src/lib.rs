@@ -2,6 +2,7 @@
 pub mod cell;
 pub mod refcell;
 pub mod rc;
+pub mod arc;
 
 // fn escape<'a>(s: &'a str) -> Cow<'a, str> {
 //     use std::borrow::Cow;
@@ -1,6 +1,11 @@
 use crate::cell::Cell;
+use std::alloc::{self, Layout};
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::marker::PhantomData;
-use std::ptr::NonNull;
+use std::mem::{self, ManuallyDrop};
+use std::ptr::{self, NonNull};
 
 // struct Foo<'a, T: Default> {
 //     v: &'a mut T,
@@ -18,21 +23,64 @@ use std::ptr::NonNull;
 //     foo = Rc::new(Foo { v: &mut t });
 // }
 
-struct SharedValue<T> {
-    value: T,
-    refcount: Cell<usize>,
+// The value has to go last, an unsized one (a slice, a str, a dyn Trait) can only be at the end.
+// repr(C) so the counts are always at the start and where the value starts only depends on its
+// alignment (see value_offset), that's all from_raw has to go on.
+#[repr(C)]
+struct SharedValue<T: ?Sized> {
+    strong: Cell<usize>,
+    // one for every Weak, plus one shared by all the Rcs together (let go of with the last Rc), so
+    // dropping a Weak while the value is being dropped can't free the memory under the last Rc
+    weak: Cell<usize>,
+    value: ManuallyDrop<T>, // dropped by hand when the last Rc goes, the Weaks keep the memory around
 }
 
-pub struct Rc<T> {
+// how far into a SharedValue the value starts, for a value with this alignment
+fn value_offset(align: usize) -> usize {
+    let counts = Layout::new::<SharedValue<()>>();
+    let value = Layout::from_size_align(0, align).unwrap();
+    counts.extend(value).unwrap().1
+}
+
+// Only ever reference the counts of a SharedValue someone else may have dropped the value in,
+// never the whole thing: a &SharedValue<T> would claim there's a valid T in there.
+// SAFETY: ptr must point at a SharedValue whose memory hasn't been freed yet
+unsafe fn counts<'a, T: ?Sized>(
+    ptr: NonNull<SharedValue<T>>,
+) -> (&'a Cell<usize>, &'a Cell<usize>) {
+    let ptr = ptr.as_ptr();
+    (&(*ptr).strong, &(*ptr).weak)
+}
+
+// SAFETY: same as counts, and the caller's weak count can't be used again after this
+unsafe fn release_weak<T: ?Sized>(ptr: NonNull<SharedValue<T>>) {
+    let (_, weak) = counts(ptr);
+    let c = weak.get();
+    weak.set(c - 1);
+    if c == 1 {
+        // no Rcs and no Weaks left, nobody can get to this anymore
+        // the value is ManuallyDrop, so this only frees the memory
+        let _ = Box::from_raw(ptr.as_ptr());
+    }
+}
+
+pub struct Rc<T: ?Sized> {
     inner: NonNull<SharedValue<T>>, // not send because of `NonNull`
     _marker: PhantomData<SharedValue<T>>, // fix for drop check
 }
 
+// Points at the value without keeping it alive, so parent <-> child links don't keep each other
+// around forever. upgrade gives an Rc back if the value is still there.
+pub struct Weak<T: ?Sized> {
+    inner: NonNull<SharedValue<T>>,
+}
+
 impl<T> Rc<T> {
-    fn new(v: T) -> Self {
+    pub fn new(v: T) -> Self {
         let inner = Box::new(SharedValue {
-            value: v,
-            refcount: Cell::new(1),
+            value: ManuallyDrop::new(v),
+            strong: Cell::new(1),
+            weak: Cell::new(1),
         });
 
         Rc {
@@ -42,13 +90,245 @@ impl<T> Rc<T> {
             _marker: PhantomData,
         }
     }
+
+    // The value back out, if this is the only Rc; otherwise the Rc is handed back untouched.
+    // Weaks don't count, they just can't upgrade anymore afterwards.
+    pub fn try_unwrap(this: Self) -> Result<T, Self> {
+        if Rc::strong_count(&this) != 1 {
+            return Err(this);
+        }
+        let this = ManuallyDrop::new(this);
+        // SAFETY: we are the only Rc, and strong goes to 0 before anyone could look at the value
+        // again, so it's ours to take. Our Drop doesn't run, so it won't be dropped twice.
+        unsafe {
+            let value = ManuallyDrop::take(&mut (*this.inner.as_ptr()).value);
+            counts(this.inner).0.set(0);
+            release_weak(this.inner);
+            Ok(value)
+        }
+    }
+
+    // like try_unwrap, but an Rc that isn't the last one just gets dropped
+    pub fn into_inner(this: Self) -> Option<T> {
+        Rc::try_unwrap(this).ok()
+    }
+}
+
+impl<T: ?Sized> Rc<T> {
+    // associated functions rather than methods, so they can't clash with methods on T
+    pub fn downgrade(this: &Self) -> Weak<T> {
+        let inner = unsafe { this.inner.as_ref() };
+        inner.weak.set(inner.weak.get() + 1);
+        Weak { inner: this.inner }
+    }
+
+    pub fn strong_count(this: &Self) -> usize {
+        unsafe { this.inner.as_ref() }.strong.get()
+    }
+
+    pub fn weak_count(this: &Self) -> usize {
+        // not counting the one the Rcs hold together
+        unsafe { this.inner.as_ref() }.weak.get() - 1
+    }
+
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        // only the address, two dyn pointers to the same thing can have different vtables
+        this.inner.as_ptr() as *const u8 == other.inner.as_ptr() as *const u8
+    }
+
+    // Only when there's no other Rc and no Weak, either of them could read the value while the
+    // &mut is alive.
+    pub fn get_mut(this: &mut Self) -> Option<&mut T> {
+        let (strong, weak) = unsafe { counts(this.inner) };
+        if strong.get() == 1 && weak.get() == 1 {
+            // SAFETY: nobody else can get to the value, and the &mut borrows this for as long
+            Some(unsafe { &mut (*this.inner.as_ptr()).value })
+        } else {
+            None
+        }
+    }
+
+    // Hands out a pointer to the value that keeps it alive like the Rc did, until it's passed back
+    // to from_raw.
+    pub fn into_raw(this: Self) -> *const T {
+        let this = ManuallyDrop::new(this);
+        // Straight from the allocation, not through a &T: a pointer made from a reference only
+        // gets to touch the value, and from_raw steps back from it to the counts.
+        // SAFETY: inner points at a live SharedValue, addr_of doesn't make a reference
+        unsafe { ptr::addr_of!((*this.inner.as_ptr()).value) as *const T }
+    }
+
+    /// # Safety
+    /// ptr must come from Rc::into_raw, of an Rc<U> where U has the same size and alignment as T
+    /// (usually U is T), and each one can only be turned back into an Rc once.
+    pub unsafe fn from_raw(ptr: *const T) -> Self {
+        // the value is still alive, so it's fine to ask it for its alignment
+        let offset = value_offset(mem::align_of_val(&*ptr));
+        // byte_sub keeps the length/vtable, which is the same for the SharedValue as for its value
+        let inner = ptr.byte_sub(offset) as *mut SharedValue<T>;
+        Rc {
+            inner: NonNull::new_unchecked(inner),
+            _marker: PhantomData,
+        }
+    }
+
+    // Rc<Concrete> to Rc<dyn Trait>. Done for you by the compiler with std's Rc, but that needs
+    // the unstable CoerceUnsized, so here you write the coercion on the raw pointer:
+    // unsafe { Rc::unsize(rc, |p| p as *const dyn Trait) }
+    /// # Safety
+    /// coerce must hand back the pointer it's given, only unsized: U is T, or T as a slice or a
+    /// dyn Trait it implements. Nothing else is checked, a pointer to a field, say, would have
+    /// the wrong thing dropped and the wrong layout freed.
+    pub unsafe fn unsize<U: ?Sized>(
+        this: Self,
+        coerce: impl FnOnce(*const T) -> *const U,
+    ) -> Rc<U> {
+        let ptr = Rc::into_raw(this);
+        let coerced = coerce(ptr);
+        debug_assert_eq!(coerced as *const u8, ptr as *const u8);
+        Rc::from_raw(coerced)
+    }
+}
+
+// the one unsizing that can be written out once for all types
+impl<T, const N: usize> From<Rc<[T; N]>> for Rc<[T]> {
+    fn from(array: Rc<[T; N]>) -> Self {
+        // SAFETY: an array to a slice of the same elements is an unsizing
+        unsafe { Rc::unsize(array, |p| p as *const [T]) }
+    }
+}
+
+// Allocates a SharedValue with the counts at 1, room for a value with the given layout, and the
+// value itself left for the caller to write. The allocation comes back as a *mut u8, fatten makes
+// that into a pointer to the whole SharedValue (by adding a length, say).
+// SAFETY: fatten must give back a pointer for which Layout::for_value would come out at
+// the same layout as value_layout, Box frees it with that in release_weak
+unsafe fn allocate<T: ?Sized>(
+    value_layout: Layout,
+    fatten: impl FnOnce(*mut u8) -> *mut SharedValue<T>,
+) -> NonNull<SharedValue<T>> {
+    let layout = Layout::new::<SharedValue<()>>()
+        .extend(value_layout)
+        .unwrap()
+        .0
+        .pad_to_align();
+    // a SharedValue is never zero sized, the counts are in there
+    let mem = alloc::alloc(layout);
+    if mem.is_null() {
+        alloc::handle_alloc_error(layout);
+    }
+    let inner = fatten(mem);
+    ptr::addr_of_mut!((*inner).strong).write(Cell::new(1));
+    ptr::addr_of_mut!((*inner).weak).write(Cell::new(1));
+    NonNull::new_unchecked(inner)
+}
+
+impl<T> From<Vec<T>> for Rc<[T]> {
+    fn from(mut v: Vec<T>) -> Self {
+        let len = v.len();
+        unsafe {
+            let inner = allocate(Layout::array::<T>(len).unwrap(), |mem| {
+                ptr::slice_from_raw_parts_mut(mem as *mut T, len) as *mut SharedValue<[T]>
+            });
+            // move the elements over, and have the Vec forget it had them
+            let dst = ptr::addr_of_mut!((*inner.as_ptr()).value) as *mut T;
+            ptr::copy_nonoverlapping(v.as_ptr(), dst, len);
+            v.set_len(0);
+            Rc {
+                inner,
+                _marker: PhantomData,
+            }
+        }
+    }
+}
+
+// going through a Vec first is a copy more than needed, but it takes care of a clone panicking
+// half way or an iterator lying about its length
+impl<T: Clone> From<&[T]> for Rc<[T]> {
+    fn from(v: &[T]) -> Self {
+        Rc::from(v.to_vec())
+    }
+}
+
+impl<T> std::iter::FromIterator<T> for Rc<[T]> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Rc::from(iter.into_iter().collect::<Vec<_>>())
+    }
+}
+
+impl From<&str> for Rc<str> {
+    fn from(s: &str) -> Self {
+        let bytes: Rc<[u8]> = Rc::from(s.as_bytes());
+        // SAFETY: str is [u8] on the inside, and the bytes came from a str so they're utf-8
+        unsafe { Rc::from_raw(Rc::into_raw(bytes) as *const str) }
+    }
+}
+
+impl From<String> for Rc<str> {
+    fn from(s: String) -> Self {
+        Rc::from(&s[..])
+    }
+}
+
+impl<T: Clone> Rc<T> {
+    // Clone on write: a &mut to the value, after cloning it into an Rc of our own if it's shared.
+    pub fn make_mut(this: &mut Self) -> &mut T {
+        if Rc::strong_count(this) != 1 {
+            *this = Rc::new(T::clone(this));
+        } else if Rc::weak_count(this) != 0 {
+            // No other Rcs, but Weaks that could upgrade later. No need to clone for them, move
+            // the value into a new allocation and leave them behind with nothing to upgrade to.
+            // SAFETY: we're the only Rc, the old one gets forgotten after so nothing reads the
+            // value from there again, and strong is 0 before the Weaks can look.
+            unsafe {
+                let value = ptr::read(&**this);
+                let old = mem::replace(this, Rc::new(value));
+                counts(old.inner).0.set(0);
+                release_weak(old.inner);
+                mem::forget(old);
+            }
+        }
+        Rc::get_mut(this).expect("just made sure we're the only one")
+    }
+}
+
+impl<T: ?Sized> Weak<T> {
+    pub fn upgrade(&self) -> Option<Rc<T>> {
+        // SAFETY: we're a Weak, so the memory is still there, even if the value may not be
+        let (strong, _) = unsafe { counts(self.inner) };
+        let c = strong.get();
+        if c == 0 {
+            // the last Rc is gone, and so is the value
+            return None;
+        }
+        strong.set(c + 1);
+        Some(Rc {
+            inner: self.inner,
+            _marker: PhantomData,
+        })
+    }
 }
 
-impl<T> Clone for Rc<T> {
+impl<T: ?Sized> Clone for Weak<T> {
+    fn clone(&self) -> Self {
+        let (_, weak) = unsafe { counts(self.inner) };
+        weak.set(weak.get() + 1);
+        Weak { inner: self.inner }
+    }
+}
+
+impl<T: ?Sized> Drop for Weak<T> {
+    fn drop(&mut self) {
+        // SAFETY: we're a Weak, so the memory is still there, and this is us going away
+        unsafe { release_weak(self.inner) };
+    }
+}
+
+impl<T: ?Sized> Clone for Rc<T> {
     fn clone(&self) -> Self {
         let inner = unsafe { self.inner.as_ref() };
-        let c = inner.refcount.get();
-        inner.refcount.set(c + 1);
+        let c = inner.strong.get();
+        inner.strong.set(c + 1);
         Rc {
             inner: self.inner,
             _marker: PhantomData,
@@ -56,7 +336,7 @@ impl<T> Clone for Rc<T> {
     }
 }
 
-impl<T> std::ops::Deref for Rc<T> {
+impl<T: ?Sized> std::ops::Deref for Rc<T> {
     type Target = T;
     fn deref(&self) -> &Self::Target {
         // SAFETY: self.inner is a `Box` that is only deallocated when the last `Rc` goes away
@@ -65,18 +345,72 @@ impl<T> std::ops::Deref for Rc<T> {
     }
 }
 
-impl<T> Drop for Rc<T> {
+// everything that just looks at the value does what T does
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for Rc<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: ?Sized + fmt::Display> fmt::Display for Rc<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+impl<T: ?Sized + PartialEq> PartialEq for Rc<T> {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl<T: ?Sized + Eq> Eq for Rc<T> {}
+
+impl<T: ?Sized + PartialOrd> PartialOrd for Rc<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        (**self).partial_cmp(&**other)
+    }
+}
+
+impl<T: ?Sized + Ord> Ord for Rc<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (**self).cmp(&**other)
+    }
+}
+
+impl<T: ?Sized + Hash> Hash for Rc<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (**self).hash(state)
+    }
+}
+
+impl<T: Default> Default for Rc<T> {
+    fn default() -> Self {
+        Rc::new(T::default())
+    }
+}
+
+impl<T> From<T> for Rc<T> {
+    fn from(v: T) -> Self {
+        Rc::new(v)
+    }
+}
+
+impl<T: ?Sized> Drop for Rc<T> {
     fn drop(&mut self) {
         let inner = unsafe { self.inner.as_ref() };
-        let c = inner.refcount.get();
+        let c = inner.strong.get();
+        // set before dropping the value, so an upgrade from inside T's drop gets None
+        inner.strong.set(c - 1);
         if c == 1 {
-            drop(inner);
             // SAFETY: we are the only `Rc` left, and we are being dropped
             // therefore after us, there will be no `Rc`s, and no references to `T`.
-            let _ = unsafe { Box::from_raw(self.inner.as_ptr()) };
-        } else {
-            // there are otehr Rc's, so don't drop the Box!
-            inner.refcount.set(c - 1);
+            // The memory stays until the Weaks are gone too.
+            unsafe {
+                ManuallyDrop::drop(&mut (*self.inner.as_ptr()).value);
+                release_weak(self.inner);
+            }
         }
     }
 }
@@ -84,4 +418,174 @@ impl<T> Drop for Rc<T> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::refcell::RefCell;
+
+    // counts its drops
+    struct Tracked<'a>(&'a Cell<usize>);
+
+    impl Drop for Tracked<'_> {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[test]
+    fn counts() {
+        let drops = Cell::new(0);
+        let a = Rc::new(Tracked(&drops));
+        let b = Rc::clone(&a);
+        let w = Rc::downgrade(&a);
+        assert_eq!(Rc::strong_count(&a), 2);
+        assert_eq!(Rc::weak_count(&a), 1);
+        drop(a);
+        assert!(w.upgrade().is_some());
+        drop(b);
+        // the value goes with the last Rc, even with a Weak still around
+        assert_eq!(drops.get(), 1);
+        assert!(w.upgrade().is_none());
+    }
+
+    struct Node<'a> {
+        parent: Option<Weak<Node<'a>>>,
+        children: RefCell<Vec<Rc<Node<'a>>>>,
+        _tracked: Tracked<'a>,
+    }
+
+    #[test]
+    fn parent_and_child() {
+        let drops = Cell::new(0);
+        {
+            let parent = Rc::new(Node {
+                parent: None,
+                children: RefCell::new(Vec::new()),
+                _tracked: Tracked(&drops),
+            });
+            let child = Rc::new(Node {
+                parent: Some(Rc::downgrade(&parent)),
+                children: RefCell::new(Vec::new()),
+                _tracked: Tracked(&drops),
+            });
+            parent.children.borrow_mut().push(Rc::clone(&child));
+            let up = child.parent.as_ref().unwrap().upgrade().unwrap();
+            assert_eq!(up.children.borrow().len(), 1);
+        }
+        // with a strong parent pointer these two would keep each other alive
+        assert_eq!(drops.get(), 2);
+    }
+
+    #[test]
+    fn unwrap() {
+        let a = Rc::new(String::from("hi"));
+        let b = Rc::clone(&a);
+        let w = Rc::downgrade(&a);
+        let a = Rc::try_unwrap(a).unwrap_err();
+        assert_eq!(Rc::into_inner(b), None);
+        assert_eq!(Rc::try_unwrap(a).unwrap(), "hi");
+        assert!(w.upgrade().is_none());
+    }
+
+    #[test]
+    fn mutate() {
+        let mut a = Rc::new(5);
+        *Rc::get_mut(&mut a).unwrap() += 1;
+        let b = Rc::clone(&a);
+        assert!(Rc::get_mut(&mut a).is_none());
+        // shared, so a gets a copy of its own
+        *Rc::make_mut(&mut a) += 1;
+        assert_eq!((*a, *b), (7, 6));
+        assert!(!Rc::ptr_eq(&a, &b));
+
+        let w = Rc::downgrade(&a);
+        assert!(Rc::get_mut(&mut a).is_none());
+        *Rc::make_mut(&mut a) += 1;
+        assert_eq!(*a, 8);
+        assert!(w.upgrade().is_none());
+        assert!(Rc::get_mut(&mut a).is_some());
+    }
+
+    // worth a run under miri after touching any of the unsafe code: cargo +nightly miri test rc::
+    #[test]
+    fn raw() {
+        let drops = Cell::new(0);
+        let a = Rc::new(Tracked(&drops));
+        let b = Rc::clone(&a);
+        let ptr = Rc::into_raw(b);
+        assert_eq!(ptr, &*a as *const _);
+        drop(a);
+        assert_eq!(drops.get(), 0);
+        let b = unsafe { Rc::from_raw(ptr) };
+        assert_eq!(Rc::strong_count(&b), 1);
+        drop(b);
+        assert_eq!(drops.get(), 1);
+    }
+
+    #[test]
+    fn forwarding() {
+        use std::collections::HashSet;
+
+        let a: Rc<i32> = Rc::from(1);
+        let b = Rc::new(2);
+        assert!(a < b);
+        assert_eq!(a, Rc::new(1));
+        assert_eq!(format!("{} {:?}", a, b), "1 2");
+        assert_eq!(*Rc::<i32>::default(), 0);
+        let set: HashSet<_> = vec![Rc::clone(&a), b, a].into_iter().collect();
+        assert_eq!(set.len(), 2);
+    }
+
+    #[test]
+    fn slices() {
+        let drops = Cell::new(0);
+        let a: Rc<[Tracked]> = (0..3).map(|_| Tracked(&drops)).collect();
+        assert_eq!(a.len(), 3);
+        let w = Rc::downgrade(&a);
+        drop(a);
+        assert_eq!(drops.get(), 3);
+        assert!(w.upgrade().is_none());
+
+        let a: Rc<[u64]> = Rc::from(&[1, 2, 3][..]);
+        let b = Rc::clone(&a);
+        assert_eq!(*b, [1, 2, 3]);
+        let empty: Rc<[String]> = Rc::from(Vec::new());
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn strs() {
+        let a: Rc<str> = Rc::from("hello");
+        let b: Rc<str> = Rc::from(String::from("hello"));
+        assert_eq!(a, b);
+        assert!(!Rc::ptr_eq(&a, &b));
+        assert_eq!(format!("{} {:?}", a, b), "hello \"hello\"");
+        let ptr = Rc::into_raw(a);
+        let a = unsafe { Rc::from_raw(ptr) };
+        assert_eq!(&*a, "hello");
+    }
+
+    trait Speak {
+        fn speak(&self) -> String;
+    }
+
+    impl Speak for Tracked<'_> {
+        fn speak(&self) -> String {
+            format!("dropped {} so far", self.0.get())
+        }
+    }
+
+    #[test]
+    fn dyn_trait() {
+        let drops = Cell::new(0);
+        let a = Rc::new(Tracked(&drops));
+        let b = Rc::clone(&a);
+        let d: Rc<dyn Speak + '_> = unsafe { Rc::unsize(a, |p| p as *const dyn Speak) };
+        assert_eq!(d.speak(), "dropped 0 so far");
+        assert_eq!(Rc::strong_count(&d), 2);
+        drop(b);
+        // dropped through the vtable
+        drop(d);
+        assert_eq!(drops.get(), 1);
+
+        let array: Rc<[u8]> = Rc::from(Rc::new([1u8, 2, 3]));
+        assert_eq!(*array, [1, 2, 3]);
+    }
 }
@@ -1,5 +1,8 @@
 use crate::cell::Cell;
 use std::cell::UnsafeCell;
+use std::fmt;
+#[cfg(debug_assertions)]
+use std::panic::Location;
 
 #[derive(Clone, Copy)]
 enum RefState {
@@ -11,46 +14,127 @@ enum RefState {
 pub struct RefCell<T> {
     value: UnsafeCell<T>,
     state: Cell<RefState>,
+    // Where the borrow in state was taken, so a conflict can point at the one it ran into. For
+    // Shared that's the latest of them: the earlier ones may have been dropped already, the latest
+    // is the likeliest to still be around. Only kept in debug builds, release ones don't pay for it.
+    #[cfg(debug_assertions)]
+    borrowed_at: Cell<Option<&'static Location<'static>>>,
 }
 
 // implied by UnsafeCell being in the struct
 // impl<T> !Sync for RefCell<T> {}
 
+// what try_borrow gives back when the value is mutably borrowed
+#[derive(Debug)]
+pub struct BorrowError {
+    #[cfg(debug_assertions)]
+    borrowed_at: Option<&'static Location<'static>>,
+}
+
+// what try_borrow_mut gives back when the value is borrowed at all
+#[derive(Debug)]
+pub struct BorrowMutError {
+    #[cfg(debug_assertions)]
+    borrowed_at: Option<&'static Location<'static>>,
+}
+
+impl fmt::Display for BorrowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("already mutably borrowed")?;
+        #[cfg(debug_assertions)]
+        if let Some(at) = self.borrowed_at {
+            write!(f, " (mutable borrow taken at {})", at)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for BorrowMutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("already borrowed")?;
+        #[cfg(debug_assertions)]
+        if let Some(at) = self.borrowed_at {
+            write!(f, " (borrow taken at {})", at)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for BorrowError {}
+impl std::error::Error for BorrowMutError {}
+
 impl<T> RefCell<T> {
     pub fn new(value: T) -> Self {
         Self {
             value: UnsafeCell::new(value),
             state: Cell::new(RefState::Unshared),
+            #[cfg(debug_assertions)]
+            borrowed_at: Cell::new(None),
+        }
+    }
+
+    // track_caller all the way down, so Location::caller() in remember is the user's call site
+    #[track_caller]
+    fn remember(&self) {
+        #[cfg(debug_assertions)]
+        self.borrowed_at.set(Some(Location::caller()));
+    }
+
+    // Panics if the value is mutably borrowed, try_borrow for when that's expected.
+    #[track_caller]
+    pub fn borrow(&self) -> Ref<'_, T> {
+        match self.try_borrow() {
+            Ok(r) => r,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    // Panics if the value is borrowed at all, try_borrow_mut for when that's expected.
+    #[track_caller]
+    pub fn borrow_mut(&self) -> RefMut<'_, T> {
+        match self.try_borrow_mut() {
+            Ok(r) => r,
+            Err(e) => panic!("{}", e),
         }
     }
 
-    pub fn borrow(&self) -> Option<Ref<'_, T>> {
+    #[track_caller]
+    pub fn try_borrow(&self) -> Result<Ref<'_, T>, BorrowError> {
         match self.state.get() {
             RefState::Unshared => {
                 self.state.set(RefState::Shared(1));
-                Some(Ref { refcell: self })
+                self.remember();
                 // SAFETY: no exclusive references have been given out since state would be
                 // Exclusive.
-                // Some(unsafe { &*self.value.get() })
+                Ok(Ref { refcell: self })
             }
             RefState::Shared(n) => {
                 self.state.set(RefState::Shared(n + 1));
+                self.remember();
                 // SAFETY: no exclusive references have been given out since state would be
                 // Exclusive.
-                // Some(unsafe { &*self.value.get() })
-                Some(Ref { refcell: self })
+                Ok(Ref { refcell: self })
             }
-            RefState::Exclusive => None,
+            RefState::Exclusive => Err(BorrowError {
+                #[cfg(debug_assertions)]
+                borrowed_at: self.borrowed_at.get(),
+            }),
         }
     }
-    pub fn borrow_mut(&self) -> Option<RefMut<'_, T>> {
+
+    #[track_caller]
+    pub fn try_borrow_mut(&self) -> Result<RefMut<'_, T>, BorrowMutError> {
         if let RefState::Unshared = self.state.get() {
             self.state.set(RefState::Exclusive);
+            self.remember();
             // SAFETY: no exclusive references have been given out since state would be
             // Shared or Exclusive.
-            Some(RefMut { refcell: self })
+            Ok(RefMut { refcell: self })
         } else {
-            None
+            Err(BorrowMutError {
+                #[cfg(debug_assertions)]
+                borrowed_at: self.borrowed_at.get(),
+            })
         }
     }
 }
@@ -70,6 +154,12 @@ impl<T> std::ops::Deref for Ref<'_, T> {
     }
 }
 
+impl<T: fmt::Debug> fmt::Debug for Ref<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
 impl<T> Drop for Ref<'_, T> {
     fn drop(&mut self) {
         match self.refcell.state.get() {
@@ -107,6 +197,12 @@ impl<T> std::ops::DerefMut for RefMut<'_, T> {
     }
 }
 
+impl<T: fmt::Debug> fmt::Debug for RefMut<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
 impl<T> Drop for RefMut<'_, T> {
     fn drop(&mut self) {
         match self.refcell.state.get() {
@@ -117,3 +213,64 @@ impl<T> Drop for RefMut<'_, T> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conflicts() {
+        let c = RefCell::new(5);
+        {
+            let a = c.borrow();
+            let b = c.borrow();
+            assert_eq!(*a + *b, 10);
+            assert!(c.try_borrow_mut().is_err());
+        }
+        {
+            let mut m = c.borrow_mut();
+            *m += 1;
+            assert!(c.try_borrow().is_err());
+            assert!(c.try_borrow_mut().is_err());
+        }
+        assert_eq!(*c.try_borrow().unwrap(), 6);
+        // same as std's, they show the value
+        assert_eq!(format!("{:?}", c.borrow()), "6");
+        assert_eq!(format!("{:?}", c.borrow_mut()), "6");
+    }
+
+    #[test]
+    #[should_panic(expected = "already mutably borrowed")]
+    fn borrow_while_mutably_borrowed() {
+        let c = RefCell::new(5);
+        let _m = c.borrow_mut();
+        let _ = c.borrow();
+    }
+
+    #[test]
+    #[should_panic(expected = "already borrowed")]
+    fn borrow_mut_while_borrowed() {
+        let c = RefCell::new(5);
+        let _r = c.borrow();
+        let _ = c.borrow_mut();
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn points_at_the_borrow() {
+        let c = RefCell::new(5);
+        let _m = c.borrow_mut();
+        let line = line!() - 1;
+        let err = c.try_borrow().unwrap_err().to_string();
+        assert!(err.contains(&format!("{}:{}:", file!(), line)), "{}", err);
+
+        // the latest shared borrow, the first one is gone by the time it conflicts
+        let c = RefCell::new(5);
+        let first = c.borrow();
+        let _second = c.borrow();
+        let line = line!() - 1;
+        drop(first);
+        let err = c.try_borrow_mut().unwrap_err().to_string();
+        assert!(err.contains(&format!("{}:{}:", file!(), line)), "{}", err);
+    }
+}
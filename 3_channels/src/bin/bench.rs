@@ -0,0 +1,45 @@
+// the channel with more and more senders fighting over the tail. run it once with
+// `cargo run --release --bin bench` (mutex + condvar) and once with `--features lockfree`
+// (the block list) to compare
+use std::thread;
+use std::time::{Duration, Instant};
+
+const MESSAGES: usize = 1_000_000;
+const FLAVOR: &str = if cfg!(feature = "lockfree") {
+    "list"
+} else {
+    "mutex"
+};
+
+fn main() {
+    for &senders in &[1, 2, 4, 8, 16] {
+        for _ in 0..5 {
+            let (tx, rx) = panama::_channel();
+            let took = bench(senders, tx, move || rx.iter().count(), |tx, i| tx.send(i).unwrap());
+            println!("{} {} {}", FLAVOR, senders, took.as_micros());
+        }
+    }
+}
+
+// every sender pushes its share of MESSAGES as fast as it can, time until the receiver has them all
+fn bench<S, R, F>(senders: usize, tx: S, receive: R, send: F) -> Duration
+where
+    S: Clone + Send + 'static,
+    R: FnOnce() -> usize + Send + 'static,
+    F: Fn(&mut S, usize) + Copy + Send + 'static,
+{
+    let start = Instant::now();
+    let receiver = thread::spawn(receive);
+    for _ in 0..senders {
+        let mut tx = tx.clone();
+        thread::spawn(move || {
+            for i in 0..MESSAGES / senders {
+                send(&mut tx, i);
+            }
+        });
+    }
+    drop(tx);
+    let received = receiver.join().unwrap();
+    assert_eq!(received, MESSAGES / senders * senders);
+    start.elapsed()
+}
@@ -0,0 +1,245 @@
+use crate::{Receiver, RecvError, SendError, Sender};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+// Futures for the same channel the blocking side uses.
+// Instead of sleeping on a condvar the future leaves the task's Waker in Inner and returns Pending,
+// whoever changes the queue wakes everything registered there (and notifies the condvars for the
+// blocking side), so a std thread can send to a task and the other way around. The list flavor
+// keeps the wakers next to the list and does the same, see list.rs. How the polling goes is up to
+// the flavor, here's just the futures.
+//
+// They take &mut so they're Send (Receiver isn't Sync), which is what tokio::spawn wants.
+
+impl<T> Receiver<T> {
+    pub fn recv_async(&mut self) -> RecvFuture<'_, T> {
+        RecvFuture { rx: self, id: None }
+    }
+}
+
+impl<T> Sender<T> {
+    // only ever waits on a bounded channel, on an unbounded one it's ready on the first poll
+    pub fn send_async(&mut self, val: T) -> SendFuture<'_, T> {
+        SendFuture {
+            tx: self,
+            val: Some(val),
+            ticket: None,
+            id: None,
+        }
+    }
+}
+
+pub struct RecvFuture<'a, T> {
+    rx: &'a mut Receiver<T>,
+    id: Option<usize>, // our registration in recv_wakers, so drop can take it back out
+}
+
+impl<T> Future for RecvFuture<'_, T> {
+    type Output = Result<T, RecvError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        this.rx.poll_recv(&mut this.id, cx.waker())
+    }
+}
+
+impl<T> Drop for RecvFuture<'_, T> {
+    fn drop(&mut self) {
+        if self.id.is_some() {
+            self.rx.shared.unregister_recv(self.id);
+        }
+    }
+}
+
+pub struct SendFuture<'a, T> {
+    tx: &'a mut Sender<T>,
+    val: Option<T>,        // None once it's been pushed (or handed back)
+    ticket: Option<usize>, // rendezvous: pushed, waiting for the receiver to take it
+    id: Option<usize>,     // our registration in send_wakers
+}
+
+// we never hand out a Pin<&mut T>, `val` just gets moved out, so T doesn't need to be Unpin
+impl<T> Unpin for SendFuture<'_, T> {}
+
+impl<T> Future for SendFuture<'_, T> {
+    type Output = Result<(), SendError<T>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        this.tx
+            .poll_send(&mut this.val, &mut this.ticket, &mut this.id, cx.waker())
+    }
+}
+
+// if this is dropped while a rendezvous send is waiting to be taken, the value stays in the
+// channel and still gets received, there's no taking it back at that point
+impl<T> Drop for SendFuture<'_, T> {
+    fn drop(&mut self) {
+        if self.id.is_some() {
+            self.tx.shared.unregister_send(self.id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::select::ThreadWaker;
+    use crate::{_channel, sync_channel};
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::task::{Wake, Waker};
+    use std::thread;
+    use std::time::Duration;
+
+    // just enough of an executor to drive one future on the current thread
+    fn block_on<F: Future>(f: F) -> F::Output {
+        let mut f = Box::pin(f);
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            if let Poll::Ready(v) = f.as_mut().poll(&mut cx) {
+                return v;
+            }
+            thread::park();
+        }
+    }
+
+    fn poll_once<F: Future + Unpin>(f: &mut F) -> Poll<F::Output> {
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        Pin::new(f).poll(&mut Context::from_waker(&waker))
+    }
+
+    #[test]
+    fn recv_from_thread() {
+        let (mut tx, mut rx) = _channel();
+        let t = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            tx.send(42).unwrap();
+        });
+        assert_eq!(block_on(rx.recv_async()), Ok(42));
+        assert_eq!(block_on(rx.recv_async()), Err(RecvError));
+        t.join().unwrap();
+    }
+
+    #[test]
+    fn send_to_thread() {
+        let (mut tx, rx) = sync_channel(1);
+        block_on(tx.send_async(1)).unwrap();
+        let t = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            rx.iter().collect::<Vec<_>>()
+        });
+        // full, so this has to wait for the thread to make room
+        block_on(tx.send_async(2)).unwrap();
+        drop(tx);
+        assert_eq!(t.join().unwrap(), vec![1, 2]);
+    }
+
+    #[test]
+    fn send_waits_for_room() {
+        let (mut tx, rx) = sync_channel(1);
+        tx.send(1).unwrap();
+        let mut send = tx.send_async(2);
+        assert!(poll_once(&mut send).is_pending());
+        assert_eq!(rx.recv(), Ok(1));
+        assert_eq!(poll_once(&mut send), Poll::Ready(Ok(())));
+        drop(send);
+        assert_eq!(rx.recv(), Ok(2));
+    }
+
+    #[test]
+    fn rendezvous() {
+        let (mut tx, rx) = sync_channel(0);
+        let mut send = tx.send_async("hi");
+        // handed over, but nobody has taken it yet
+        assert!(poll_once(&mut send).is_pending());
+        assert_eq!(rx.recv(), Ok("hi"));
+        assert_eq!(poll_once(&mut send), Poll::Ready(Ok(())));
+    }
+
+    #[test]
+    fn closed_rx() {
+        let (mut tx, rx) = sync_channel(0);
+        let mut send = tx.send_async(String::from("hi"));
+        assert!(poll_once(&mut send).is_pending());
+        drop(rx);
+        assert_eq!(
+            poll_once(&mut send),
+            Poll::Ready(Err(SendError(String::from("hi"))))
+        );
+    }
+
+    #[test]
+    fn dropped_future_unregisters() {
+        let (_tx, mut rx) = _channel::<()>();
+        let mut recv = rx.recv_async();
+        assert!(poll_once(&mut recv).is_pending());
+        drop(recv);
+        assert!(rx.shared.recv_wakers_empty());
+    }
+
+    struct Counting(AtomicUsize);
+
+    impl Wake for Counting {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn same_task_different_futures() {
+        // like two sends in one join!: same waker, and one of them going away early
+        let (mut tx1, rx) = sync_channel(1);
+        let mut tx2 = tx1.clone();
+        tx1.send(1).unwrap();
+        let count = Arc::new(Counting(AtomicUsize::new(0)));
+        let waker = Waker::from(count.clone());
+        let mut cx = Context::from_waker(&waker);
+        let mut send1 = tx1.send_async(2);
+        let mut send2 = tx2.send_async(3);
+        assert!(Pin::new(&mut send1).poll(&mut cx).is_pending());
+        assert!(Pin::new(&mut send2).poll(&mut cx).is_pending());
+        drop(send1);
+        assert!(!rx.shared.send_wakers_empty());
+        assert_eq!(rx.recv(), Ok(1));
+        assert_eq!(count.0.load(Ordering::Relaxed), 1);
+        assert_eq!(Pin::new(&mut send2).poll(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(rx.recv(), Ok(3));
+    }
+
+    // an executor polling right there in wake, all it needs to get going is the lock
+    struct Inline<T> {
+        tx: Sender<T>,
+        got_lock: AtomicBool,
+    }
+
+    impl<T: Send> Wake for Inline<T> {
+        fn wake(self: Arc<Self>) {
+            self.wake_by_ref();
+        }
+
+        fn wake_by_ref(self: &Arc<Self>) {
+            let locked = self.tx.shared.is_locked();
+            self.got_lock.store(!locked, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn woken_without_the_lock() {
+        let (mut tx, mut rx) = _channel();
+        let inline = Arc::new(Inline {
+            tx: tx.clone(),
+            got_lock: AtomicBool::new(false),
+        });
+        let waker = Waker::from(inline.clone());
+        let mut recv = rx.recv_async();
+        assert!(Pin::new(&mut recv)
+            .poll(&mut Context::from_waker(&waker))
+            .is_pending());
+        tx.send(1).unwrap();
+        assert!(inline.got_lock.load(Ordering::Relaxed));
+        assert_eq!(poll_once(&mut recv), Poll::Ready(Ok(1)));
+    }
+}
@@ -1,155 +1,418 @@
+#[cfg(feature = "lockfree")]
+use std::cell::Cell;
+#[cfg(not(feature = "lockfree"))]
+use std::cell::RefCell;
+#[cfg(not(feature = "lockfree"))]
 use std::collections::VecDeque;
-use std::sync::{Arc, Condvar, Mutex};
+#[cfg(feature = "lockfree")]
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+mod error;
+mod future;
+#[cfg(feature = "lockfree")]
+mod list;
+#[cfg(not(feature = "lockfree"))]
+mod mutex;
+mod oneshot;
+mod select;
+mod stats;
+
+pub use error::{
+    ReadyTimeoutError, RecvError, RecvTimeoutError, SendError, TryReadyError, TryRecvError,
+};
+pub use future::{RecvFuture, SendFuture};
+pub use oneshot::{oneshot, OneshotReceiver, OneshotSender};
+pub use select::Select;
+pub use stats::Stats;
+
+// What's behind Sender and Receiver: a VecDeque under a Mutex + Condvar (mutex.rs), or with the
+// `lockfree` feature the block linked list (list.rs). Same API either way, so the one can be
+// swapped for the other to compare them.
+#[cfg(feature = "lockfree")]
+use list::{channel_with_capacity, Shared};
+#[cfg(not(feature = "lockfree"))]
+use mutex::{channel_with_capacity, Shared};
 
 // Channel Flavors:
 // Synchronous: send can block, (bounded) limited capacity,
-//  - Mutex + Condvar: VecDeque have sender block if full
+//  - Mutex + Condvar: VecDeque have sender block if full (sync_channel)
 //  - Atomic VecDeque/queue: head/tail pointers updated atomically, thread::park + thread::Thread::notify primitive - system for waking up blocking channels
 // Asynchronous: send cannot block, unbounded capcity
 //  - Mutex + Condvar + VecDeque: what we made here
 //  - Mutex + Condvar + LinkedList (to prevent resizing): sender appends to list, receiver takes the head and walks backwards, only need tail pointer
 //  - Atomic Queue/LinkedList: linked list of T
 //  - Atomic Block LinkedList (crossbeam): linked list of atomic VecDeqeue<T> to prevent one sender blocking when two try to send and update LL tail pointer at the same time
-// Rendezvous: synchronous channel with capacity = 0, used to
+//    - see list.rs, what Sender/Receiver are built on with the `lockfree` feature
+// Rendezvous: synchronous channel with capacity = 0 (sync_channel(0)), used to
 //             synchronize two threads rather than for sending 
 //             values, type is often ()
 // Oneshot Channels: any capacity, only one call to send - can atomically swap in and out of a place in memory that's either Some or None
+//  - see oneshot.rs
 
 // async/await
 // hard to write one that works for both futures and blocking as futures want to return when waking up
 // different sync primitives
 // hard to write an implementation that internally knows whether it's being used in async futures context or blocking channels context without exposing to user
+//  - so we expose it: recv/send block on the condvars, recv_async/send_async leave a Waker
+//    in Inner instead (see future.rs), and both get woken by the other side either way
 
 pub struct Sender<T> {
     shared: Arc<Shared<T>>,
 }
 
-// need to implement manually so that the Clone trait bound is not there
-// we don't need it since we are using an Arc
-impl<T> Clone for Sender<T> {
-    fn clone(&self) -> Self {
-        let mut inner = self.shared.inner.lock().unwrap();
-        inner.senders += 1;
-        drop(inner);
-
-        Sender {
-            // use Arc::clone() to explicitly say you want to clone the Arc and not the thing inside of it,
-            // the dot operator deref coercion would call wrong clone method
-            shared: Arc::clone(&self.shared),
-        }
-    }
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+    // optimization don't need to take lock for every receive
+    // RefCell so receiving only needs &self (and `for msg in &rx` works), it also keeps Receiver !Sync
+    #[cfg(not(feature = "lockfree"))]
+    buffer: RefCell<VecDeque<T>>,
+    // no lock to save on, so nothing to buffer, but Receiver stays !Sync all the same
+    #[cfg(feature = "lockfree")]
+    _not_sync: PhantomData<Cell<()>>,
 }
 
-impl<T> Drop for Sender<T> {
-    fn drop(&mut self) {
-        let mut inner = self.shared.inner.lock().unwrap();
-        inner.senders -= 1;
-        let was_last = inner.senders == 0;
-        drop(inner);
-        if was_last {
-            self.shared.available.notify_one();
+impl<T> Receiver<T> {
+    pub fn recv(&self) -> Result<T, RecvError> {
+        match self.recv_until(None) {
+            Ok(t) => Ok(t),
+            Err(RecvTimeoutError::Disconnected) => Err(RecvError),
+            Err(RecvTimeoutError::Timeout) => unreachable!("no deadline to time out on"),
         }
     }
-}
 
-impl<T> Sender<T> {
-    pub fn send(&mut self, val: T) {
-        let mut inner = self.shared.inner.lock().unwrap();
-        inner.queue.push_back(val);
-        drop(inner); // drop lock so the thing being notified can wake up and use it right away
-                     // Notify that it's time to wake up as there is work to be done
-        self.shared.available.notify_one(); // doesn't notify specific threads, can be any thread
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        self.recv_deadline(Instant::now() + timeout)
+    }
+
+    pub fn recv_deadline(&self, deadline: Instant) -> Result<T, RecvTimeoutError> {
+        self.recv_until(Some(deadline))
     }
 }
 
-pub struct Receiver<T> {
-    shared: Arc<Shared<T>>,
-    buffer: VecDeque<T>, // optimization don't need to take lock for every receive
+pub fn _channel<T>() -> (Sender<T>, Receiver<T>) {
+    channel_with_capacity(None)
+}
+
+// send blocks once `capacity` values are queued up and waiting for the receiver
+// with a capacity of 0 every send blocks until the receiver has taken the value (rendezvous)
+pub fn sync_channel<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    channel_with_capacity(Some(capacity))
 }
 
 impl<T> Receiver<T> {
-    pub fn recv(&mut self) -> Option<T> {
-        if let Some(t) = self.buffer.pop_front() {
-            return Some(t);
-        }
+    // blocks waiting for the next value, ends once every sender is gone and everything is received
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter { rx: self }
+    }
 
-        let mut inner = self.shared.inner.lock().unwrap();
-        loop {
-            match inner.queue.pop_front() {
-                Some(t) => {
-                    // optimization to not need to take the lock to receive every send one by one
-                    if !inner.queue.is_empty() {
-                        std::mem::swap(&mut self.buffer, &mut inner.queue); // take everything that has been sent at once
-                    }
-                    return Some(t);
-                }
-                // None if Arc::strong_count(&self.shared) == 1 => return None, // make sure the arc rc is 1 meaning there's no senders (since the 1 is the recveiver)
-                None if inner.senders == 0 => return None, 
-                None => {
-                    // thread goes to sleep until condvar wakes it up
-                    // OS doesn't guarentee you aren't woken up with no work
-                    // can be woken up for no reason
-                    inner = self.shared.available.wait(inner).unwrap();
-                }
-            }
-        }
+    // never blocks, ends as soon as there's nothing left to receive right now
+    pub fn try_iter(&self) -> TryIter<'_, T> {
+        TryIter { rx: self }
     }
 }
 
-struct Inner<T> {
-    queue: VecDeque<T>,
-    senders: usize,
+pub struct Iter<'a, T> {
+    rx: &'a Receiver<T>,
 }
 
-struct Shared<T> {
-    inner: Mutex<Inner<T>>,
-    available: Condvar,
+impl<T> Iterator for Iter<'_, T> {
+    type Item = T;
+    fn next(&mut self) -> Option<Self::Item> {
+        // disconnected is the only way recv fails, which is exactly when iteration should end
+        self.rx.recv().ok()
+    }
 }
 
-pub fn _channel<T>() -> (Sender<T>, Receiver<T>) {
-    let inner = Inner {
-        queue: VecDeque::default(),
-        senders: 1,
-    };
-    let shared = Shared {
-        inner: Mutex::new(inner),
-        available: Condvar::default(),
-    };
-    let shared = Arc::new(shared);
-    (Sender { shared: shared.clone() }, Receiver { shared: shared.clone(), buffer: VecDeque::default() })
+pub struct TryIter<'a, T> {
+    rx: &'a Receiver<T>,
+}
+
+impl<T> Iterator for TryIter<'_, T> {
+    type Item = T;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.rx.try_recv().ok()
+    }
 }
 
-// Example of an iterator over `Receiver<T>`
-// impl<T> Iterator for Receiver<T> {
-//     type Item = T;
-//     fn next(&mut self) -> Option<Self::Item> {
-//         // since we're using Option on the recv method this is trivial
-//         self.recv()
-//     }
-// }
+pub struct IntoIter<T> {
+    rx: Receiver<T>,
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.rx.recv().ok()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a Receiver<T> {
+    type Item = T;
+    type IntoIter = Iter<'a, T>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T> IntoIterator for Receiver<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter { rx: self }
+    }
+}
 
 #[cfg(test)]
 mod tests {
-use super::*;
+    use super::*;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::thread;
 
     #[test]
     fn ping_pong() {
-        let (mut tx, mut rx) = _channel();
-        tx.send(42);
-        assert_eq!(rx.recv(), Some(42));
+        let (mut tx, rx) = _channel();
+        tx.send(42).unwrap();
+        assert_eq!(rx.recv(), Ok(42));
     }
 
     #[test]
     fn closed_tx() {
-        let (tx, mut rx) = _channel::<()>();
+        let (tx, rx) = _channel::<()>();
         drop(tx);
-        assert_eq!(rx.recv(), None);
+        assert_eq!(rx.recv(), Err(RecvError));
     }
 
     #[test]
     fn closed_rx() {
         let (mut tx, rx) = _channel();
         drop(rx);
-        tx.send(42);
+        assert_eq!(tx.send(42), Err(SendError(42)));
+    }
+
+    #[test]
+    fn try_recv() {
+        let (mut tx, rx) = _channel();
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+        tx.send(1).unwrap();
+        tx.send(2).unwrap();
+        assert_eq!(rx.try_recv(), Ok(1));
+        drop(tx);
+        // still get what was sent before the sender went away
+        assert_eq!(rx.try_recv(), Ok(2));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+    }
+
+    #[test]
+    fn recv_timeout() {
+        let (mut tx, rx) = _channel();
+        assert_eq!(
+            rx.recv_timeout(Duration::from_millis(10)),
+            Err(RecvTimeoutError::Timeout)
+        );
+        let t = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            tx.send(1).unwrap();
+        });
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok(1));
+        t.join().unwrap();
+        assert_eq!(
+            rx.recv_deadline(Instant::now() + Duration::from_secs(5)),
+            Err(RecvTimeoutError::Disconnected)
+        );
+    }
+
+    #[test]
+    fn bounded_ping_pong() {
+        let (mut tx, rx) = sync_channel(2);
+        tx.send(1).unwrap();
+        tx.send(2).unwrap();
+        assert_eq!(rx.recv(), Ok(1));
+        tx.send(3).unwrap();
+        assert_eq!(rx.recv(), Ok(2));
+        assert_eq!(rx.recv(), Ok(3));
+    }
+
+    #[test]
+    fn bounded_blocks_when_full() {
+        let (mut tx, rx) = sync_channel(1);
+        let sent = Arc::new(AtomicUsize::new(0));
+        let sent2 = Arc::clone(&sent);
+        let t = thread::spawn(move || {
+            for i in 0..3 {
+                tx.send(i).unwrap();
+                sent2.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+        thread::sleep(Duration::from_millis(100));
+        // only one fits in the queue, the second send is stuck
+        assert_eq!(sent.load(Ordering::SeqCst), 1);
+        assert_eq!(rx.recv(), Ok(0));
+        assert_eq!(rx.recv(), Ok(1));
+        assert_eq!(rx.recv(), Ok(2));
+        t.join().unwrap();
+        assert_eq!(rx.recv(), Err(RecvError));
+    }
+
+    #[test]
+    fn rendezvous() {
+        let (mut tx, rx) = sync_channel(0);
+        let done = Arc::new(AtomicBool::new(false));
+        let done2 = Arc::clone(&done);
+        let t = thread::spawn(move || {
+            tx.send(()).unwrap();
+            done2.store(true, Ordering::SeqCst);
+        });
+        thread::sleep(Duration::from_millis(100));
+        // nobody has received yet, so the sender must still be stuck in send
+        assert!(!done.load(Ordering::SeqCst));
+        assert_eq!(rx.recv(), Ok(()));
+        t.join().unwrap();
+        assert!(done.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn bounded_closed_rx_unblocks_sender() {
+        let (mut tx, rx) = sync_channel(1);
+        tx.send(1).unwrap();
+        let t = thread::spawn(move || tx.send(2));
+        thread::sleep(Duration::from_millis(50));
+        drop(rx);
+        assert_eq!(t.join().unwrap(), Err(SendError(2)));
+    }
+
+    #[test]
+    fn rendezvous_closed_rx_returns_value() {
+        let (mut tx, rx) = sync_channel(0);
+        let t = thread::spawn(move || tx.send(String::from("hi")));
+        thread::sleep(Duration::from_millis(50));
+        drop(rx);
+        assert_eq!(t.join().unwrap(), Err(SendError(String::from("hi"))));
+    }
+
+    #[test]
+    fn rendezvous_many_senders() {
+        let (tx, rx) = sync_channel(0);
+        let handles: Vec<_> = (0..4)
+            .map(|i| {
+                let mut tx = tx.clone();
+                thread::spawn(move || {
+                    for j in 0..10 {
+                        tx.send(i * 10 + j).unwrap();
+                    }
+                })
+            })
+            .collect();
+        drop(tx);
+        let mut got: Vec<_> = rx.into_iter().collect();
+        for h in handles {
+            h.join().unwrap();
+        }
+        got.sort_unstable();
+        assert_eq!(got, (0..40).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn iter() {
+        let (mut tx, rx) = _channel();
+        let t = thread::spawn(move || {
+            for i in 0..5 {
+                tx.send(i).unwrap();
+            }
+        });
+        let mut got = Vec::new();
+        for i in &rx {
+            got.push(i);
+        }
+        t.join().unwrap();
+        assert_eq!(got, vec![0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn try_iter() {
+        let (mut tx, rx) = _channel();
+        tx.send(1).unwrap();
+        tx.send(2).unwrap();
+        // the first recv steals the rest of the queue into the local buffer,
+        // try_iter has to drain that too
+        assert_eq!(rx.recv(), Ok(1));
+        tx.send(3).unwrap();
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![2, 3]);
+        // the sender is still around, try_iter just stops instead of waiting
+        assert_eq!(rx.try_iter().next(), None);
+    }
+
+    #[test]
+    fn into_iter() {
+        let (mut tx, rx) = _channel();
+        tx.send(1).unwrap();
+        tx.send(2).unwrap();
+        drop(tx);
+        assert_eq!(rx.into_iter().collect::<Vec<_>>(), vec![1, 2]);
+    }
+
+    #[test]
+    fn mpmc() {
+        let (tx, rx) = _channel();
+        let workers: Vec<_> = (0..4)
+            .map(|_| {
+                let rx = rx.clone();
+                thread::spawn(move || rx.into_iter().collect::<Vec<_>>())
+            })
+            .collect();
+        drop(rx);
+        let senders: Vec<_> = (0..4)
+            .map(|i| {
+                let mut tx = tx.clone();
+                thread::spawn(move || {
+                    for j in 0..1000 {
+                        tx.send(i * 1000 + j).unwrap();
+                    }
+                })
+            })
+            .collect();
+        drop(tx);
+        for s in senders {
+            s.join().unwrap();
+        }
+        // every value went to exactly one worker
+        let mut got: Vec<_> = workers.into_iter().flat_map(|w| w.join().unwrap()).collect();
+        got.sort_unstable();
+        assert_eq!(got, (0..4000).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn mpmc_no_steal() {
+        let (mut tx, rx) = _channel();
+        let rx2 = rx.clone();
+        tx.send(1).unwrap();
+        tx.send(2).unwrap();
+        // with a single receiver this would have taken 2 into the local buffer
+        assert_eq!(rx.recv(), Ok(1));
+        assert_eq!(rx2.try_recv(), Ok(2));
+    }
+
+    #[test]
+    fn mpmc_drop_hands_back_buffer() {
+        let (mut tx, rx) = _channel();
+        tx.send(1).unwrap();
+        tx.send(2).unwrap();
+        tx.send(3).unwrap();
+        // steals 2 and 3 while it's the only receiver
+        assert_eq!(rx.recv(), Ok(1));
+        let rx2 = rx.clone();
+        tx.send(4).unwrap();
+        drop(rx);
+        assert_eq!(rx2.try_iter().collect::<Vec<_>>(), vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn mpmc_closed_rx() {
+        let (mut tx, rx) = _channel();
+        let rx2 = rx.clone();
+        drop(rx);
+        tx.send(1).unwrap();
+        drop(rx2);
+        assert_eq!(tx.send(2), Err(SendError(2)));
     }
 }
@@ -0,0 +1,742 @@
+use crate::select::{ThreadWaker, Wakers};
+use crate::{Receiver, RecvError, RecvTimeoutError, SendError, Sender, Stats, TryRecvError};
+use std::cell::UnsafeCell;
+use std::marker::PhantomData;
+use std::mem::MaybeUninit;
+use std::ptr;
+use std::sync::atomic::{self, AtomicBool, AtomicPtr, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Poll, Waker};
+use std::thread;
+use std::time::Instant;
+
+// Atomic Block LinkedList (crossbeam "list" flavor), what Sender and Receiver are built on with
+// the `lockfree` feature.
+// Values go into blocks of BLOCK_CAP slots, blocks are linked together, so senders never
+// wait on a lock and we only allocate once every BLOCK_CAP sends instead of once per send.
+//
+// Every send claims an index by bumping `tail.index`, index % LAP says which slot in the
+// current block. Offset BLOCK_CAP isn't a real slot, it means "the block is full and whoever
+// took the last slot is installing the next one", other senders wait for that to finish.
+// Receivers do the same on the head side, then wait for the value if its sender has claimed the
+// slot but isn't done writing it yet.
+//
+// Receivers can finish reading their slots in any order, so a block can't just be freed by
+// whoever reads its last slot: every slot says when it's been read (READ), and the one freeing
+// the block checks them all. If one is still being read it marks it (DESTROY) and leaves the
+// rest to that slot's reader.
+//
+// Only waiting takes a lock: a receiver with nothing to receive (or a sender on a full bounded
+// channel) leaves a Waker, the same as Select and the futures do, and parks. The other side only
+// goes for the lock if somebody might be waiting.
+
+const LAP: usize = 32;
+const BLOCK_CAP: usize = LAP - 1;
+
+// bits in Slot::state
+const WRITE: usize = 1; // the sender has finished writing `value`
+const READ: usize = 2; // the receiver has finished reading it
+const DESTROY: usize = 4; // the block is being freed, whoever reads this slot finishes the job
+
+struct Slot<T> {
+    value: UnsafeCell<MaybeUninit<T>>,
+    state: AtomicUsize,
+}
+
+impl<T> Slot<T> {
+    // the slot is claimed as soon as the sender has bumped the tail, the value can still be on
+    // its way, it's about to
+    fn wait_write(&self) {
+        while self.state.load(Ordering::Acquire) & WRITE == 0 {
+            thread::yield_now();
+        }
+    }
+}
+
+struct Block<T> {
+    next: AtomicPtr<Block<T>>,
+    slots: [Slot<T>; BLOCK_CAP],
+}
+
+impl<T> Block<T> {
+    fn new() -> Box<Self> {
+        Box::new(Block {
+            next: AtomicPtr::new(ptr::null_mut()),
+            slots: std::array::from_fn(|_| Slot {
+                value: UnsafeCell::new(MaybeUninit::uninit()),
+                state: AtomicUsize::new(0),
+            }),
+        })
+    }
+
+    // the sender that took the last slot links up the next block right after claiming it
+    fn wait_next(&self) -> *mut Block<T> {
+        loop {
+            let next = self.next.load(Ordering::Acquire);
+            if !next.is_null() {
+                return next;
+            }
+            thread::yield_now();
+        }
+    }
+
+    // Frees the block once every slot from `start` on has been read: called with 0 by whoever
+    // read the last slot, or by the reader of a slot marked DESTROY with the slot after it.
+    //
+    // SAFETY: this must be the block's last slot read, or a DESTROY one, so nobody else frees it
+    unsafe fn destroy(this: *mut Self, start: usize) {
+        // the last slot is the one that started all this, it's read already
+        for i in start..BLOCK_CAP - 1 {
+            let slot = &(*this).slots[i];
+            // still being read: its reader sees DESTROY when it's done and carries on from there
+            if slot.state.load(Ordering::Acquire) & READ == 0
+                && slot.state.fetch_or(DESTROY, Ordering::AcqRel) & READ == 0
+            {
+                return;
+            }
+        }
+        drop(Box::from_raw(this));
+    }
+}
+
+struct Position<T> {
+    index: AtomicUsize,
+    block: AtomicPtr<Block<T>>,
+}
+
+// a head or tail index at offset BLOCK_CAP is on its way to the next block, treat it as there
+fn settled(index: usize) -> usize {
+    if index % LAP == BLOCK_CAP {
+        index.wrapping_add(1)
+    } else {
+        index
+    }
+}
+
+// The wakers of everyone waiting on one side, and whether there might be any. The other side
+// looks at `maybe` on every operation and only takes the lock if it's set.
+struct Waiting {
+    wakers: Mutex<Wakers>,
+    maybe: AtomicBool,
+}
+
+impl Waiting {
+    fn new() -> Self {
+        Waiting {
+            wakers: Mutex::new(Wakers::default()),
+            maybe: AtomicBool::new(false),
+        }
+    }
+
+    // Register, then check for whatever it is you're waiting on: anything that happens after the
+    // check finds the waker here.
+    fn register(&self, id: &mut Option<usize>, waker: &Waker) {
+        self.wakers.lock().unwrap().register(id, waker);
+        self.maybe.store(true, Ordering::Relaxed);
+        // pairs with the fence in wake: either it sees `maybe`, or our check sees whatever it
+        // did before waking
+        atomic::fence(Ordering::SeqCst);
+    }
+
+    fn unregister(&self, id: Option<usize>) {
+        if id.is_some() {
+            self.wakers.lock().unwrap().unregister(id);
+        }
+    }
+
+    fn wake(&self) {
+        atomic::fence(Ordering::SeqCst);
+        if !self.maybe.load(Ordering::Relaxed) {
+            return;
+        }
+        let mut woken = Vec::new();
+        let mut wakers = self.wakers.lock().unwrap();
+        wakers.take(&mut woken);
+        self.maybe.store(false, Ordering::Relaxed);
+        drop(wakers);
+        // not under the lock, a woken future can get polled right there and register again
+        woken.into_iter().for_each(Waker::wake);
+    }
+}
+
+pub(crate) struct Shared<T> {
+    head: Position<T>,
+    tail: Position<T>,
+    capacity: Option<usize>, // None means unbounded, Some(0) is a rendezvous channel
+    // bounded: values sent and not received yet. A sender takes its place in here before it
+    // pushes, so two of them can't both go for the last free one
+    queued: AtomicUsize,
+    senders: AtomicUsize,
+    receivers: AtomicUsize,
+    recv_waiting: Waiting, // woken when there's something to receive, or the senders are gone
+    send_waiting: Waiting, // woken when a value got taken (room, for bounded), or the receivers are gone
+    // for stats(), `received` also tells a rendezvous send when its value has been taken
+    sent: AtomicUsize,
+    received: AtomicUsize,
+    peak_len: AtomicUsize,
+    waits: AtomicUsize,
+}
+
+// SAFETY: every slot is handed from exactly one sender to exactly one receiver through its
+// state, and blocks are only freed once every slot in them has been read
+unsafe impl<T: Send> Send for Shared<T> {}
+unsafe impl<T: Send> Sync for Shared<T> {}
+
+impl<T> Shared<T> {
+    fn push(&self, val: T) {
+        let tail = &self.tail;
+        let mut index = tail.index.load(Ordering::Acquire);
+        let mut block = tail.block.load(Ordering::Acquire);
+        let mut next_block = None;
+        loop {
+            let offset = index % LAP;
+            if offset == BLOCK_CAP {
+                // someone took the last slot and is installing the next block, wait for them
+                thread::yield_now();
+                index = tail.index.load(Ordering::Acquire);
+                block = tail.block.load(Ordering::Acquire);
+                continue;
+            }
+
+            // about to take the last slot, allocate the next block before claiming it
+            // so the other senders spend as little time as possible waiting on us
+            if offset + 1 == BLOCK_CAP && next_block.is_none() {
+                next_block = Some(Block::new());
+            }
+
+            match tail.index.compare_exchange_weak(
+                index,
+                index.wrapping_add(1),
+                Ordering::SeqCst,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => {
+                    // SAFETY: the index only moves forward, so claiming it means `block` is
+                    // still the tail block (it's stored before the index moves into it),
+                    // and it can't be freed until a receiver has read our slot
+                    unsafe {
+                        if offset + 1 == BLOCK_CAP {
+                            let next_block = Box::into_raw(next_block.take().unwrap());
+                            tail.block.store(next_block, Ordering::Release);
+                            // skip the BLOCK_CAP offset, straight to the first slot of the next lap
+                            tail.index.store(index.wrapping_add(2), Ordering::Release);
+                            (*block).next.store(next_block, Ordering::Release);
+                        }
+                        let slot = &(*block).slots[offset];
+                        (*slot.value.get()).write(val);
+                        // fetch_or, the block could be getting freed already and have DESTROY on it
+                        slot.state.fetch_or(WRITE, Ordering::Release);
+                    }
+                    break;
+                }
+                Err(current) => {
+                    index = current;
+                    block = tail.block.load(Ordering::Acquire);
+                }
+            }
+        }
+
+        let sent = self.sent.fetch_add(1, Ordering::Relaxed) + 1;
+        // counts what's on its way to a receiver too, same as the mutex version
+        let len = sent.saturating_sub(self.received.load(Ordering::Relaxed));
+        self.peak_len.fetch_max(len, Ordering::Relaxed);
+        self.recv_waiting.wake();
+    }
+
+    // the next value, or None if there's nothing there right now
+    fn pop(&self) -> Option<T> {
+        let head = &self.head;
+        let mut index = head.index.load(Ordering::Acquire);
+        let mut block = head.block.load(Ordering::Acquire);
+        loop {
+            let offset = index % LAP;
+            if offset == BLOCK_CAP {
+                // someone took the last slot and is moving on to the next block, wait for them
+                thread::yield_now();
+                index = head.index.load(Ordering::Acquire);
+                block = head.block.load(Ordering::Acquire);
+                continue;
+            }
+
+            // nobody has claimed this slot yet, so the channel really is empty
+            if self.tail.index.load(Ordering::SeqCst) == index {
+                return None;
+            }
+
+            match head.index.compare_exchange_weak(
+                index,
+                index.wrapping_add(1),
+                Ordering::SeqCst,
+                Ordering::Acquire,
+            ) {
+                // SAFETY: same as push, claiming the index means `block` is the head block, and
+                // it isn't freed before we're done with our slot
+                Ok(_) => unsafe {
+                    if offset + 1 == BLOCK_CAP {
+                        let next = (*block).wait_next();
+                        head.block.store(next, Ordering::Release);
+                        head.index.store(index.wrapping_add(2), Ordering::Release);
+                    }
+                    let slot = &(*block).slots[offset];
+                    slot.wait_write();
+                    let t = (*slot.value.get()).assume_init_read();
+                    if offset + 1 == BLOCK_CAP {
+                        Block::destroy(block, 0);
+                    } else if slot.state.fetch_or(READ, Ordering::AcqRel) & DESTROY != 0 {
+                        Block::destroy(block, offset + 1);
+                    }
+                    return Some(t);
+                },
+                Err(current) => {
+                    index = current;
+                    block = head.block.load(Ordering::Acquire);
+                }
+            }
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        let head = settled(self.head.index.load(Ordering::SeqCst));
+        settled(self.tail.index.load(Ordering::SeqCst)) == head
+    }
+
+    // bounded: take a place in the queue, false if it's full
+    fn reserve(&self) -> bool {
+        let capacity = self.capacity.expect("only bounded channels reserve").max(1);
+        let mut queued = self.queued.load(Ordering::Relaxed);
+        while queued < capacity {
+            // Acquire: whoever made the room counted the value it took in `received` first
+            match self.queued.compare_exchange_weak(
+                queued,
+                queued + 1,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return true,
+                Err(actual) => queued = actual,
+            }
+        }
+        false
+    }
+
+    // a receiver got a value out
+    fn received_one(&self) {
+        self.received.fetch_add(1, Ordering::Release);
+        if self.capacity.is_some() {
+            self.queued.fetch_sub(1, Ordering::Release);
+            // there's room now, and a rendezvous sender may be waiting for exactly this one
+            self.send_waiting.wake();
+        }
+    }
+
+    // One go at sending, without waiting: Pending means come back once woken. `val` is taken
+    // once it's been pushed, `ticket` is set while a rendezvous send waits for it to be taken.
+    fn try_send(
+        &self,
+        val: &mut Option<T>,
+        ticket: &mut Option<usize>,
+    ) -> Poll<Result<(), SendError<T>>> {
+        if ticket.is_none() {
+            if self.receivers.load(Ordering::Acquire) == 0 {
+                let val = val.take().expect("SendFuture polled after completion");
+                return Poll::Ready(Err(SendError(val)));
+            }
+            if self.capacity.is_some() && !self.reserve() {
+                return Poll::Pending;
+            }
+            let val = val.take().expect("SendFuture polled after completion");
+            if self.capacity != Some(0) {
+                self.push(val);
+                return Poll::Ready(Ok(()));
+            }
+            // rendezvous: ours is the only value queued, so it's the next one received. worked
+            // out before pushing, a receiver could take it (and count it) right away
+            *ticket = Some(self.received.load(Ordering::Acquire) + 1);
+            self.push(val);
+        }
+
+        let t = ticket.expect("set above");
+        if self.received.load(Ordering::Acquire) >= t {
+            return Poll::Ready(Ok(()));
+        }
+        if self.receivers.load(Ordering::Acquire) == 0 {
+            // the last receiver could have taken it on its way out, look again now that
+            // they're all gone
+            if self.received.load(Ordering::Acquire) >= t {
+                return Poll::Ready(Ok(()));
+            }
+            // they left without taking it, and a rendezvous queue only ever holds one value,
+            // so the one in there is ours
+            let val = self.pop().expect("our value is still queued");
+            self.queued.fetch_sub(1, Ordering::Release);
+            *ticket = None;
+            return Poll::Ready(Err(SendError(val)));
+        }
+        Poll::Pending
+    }
+
+    // Select and the futures leave their wakers through these
+    pub(crate) fn register_recv(&self, id: &mut Option<usize>, waker: &Waker) {
+        self.recv_waiting.register(id, waker);
+    }
+
+    pub(crate) fn unregister_recv(&self, id: Option<usize>) {
+        self.recv_waiting.unregister(id);
+    }
+
+    pub(crate) fn register_send(&self, id: &mut Option<usize>, waker: &Waker) {
+        self.send_waiting.register(id, waker);
+    }
+
+    pub(crate) fn unregister_send(&self, id: Option<usize>) {
+        self.send_waiting.unregister(id);
+    }
+
+    // no lock to take it under, every number is read on its own and they can be a little out
+    // of step with each other
+    pub(crate) fn stats(&self) -> Stats {
+        let received = self.received.load(Ordering::Relaxed);
+        let sent = self.sent.load(Ordering::Relaxed);
+        Stats {
+            len: sent.saturating_sub(received),
+            peak_len: self.peak_len.load(Ordering::Relaxed),
+            senders: self.senders.load(Ordering::Relaxed),
+            receivers: self.receivers.load(Ordering::Relaxed),
+            sent,
+            received,
+            waits: self.waits.load(Ordering::Relaxed),
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn recv_wakers_empty(&self) -> bool {
+        self.recv_waiting.wakers.lock().unwrap().is_empty()
+    }
+
+    #[cfg(test)]
+    pub(crate) fn send_wakers_empty(&self) -> bool {
+        self.send_waiting.wakers.lock().unwrap().is_empty()
+    }
+
+    #[cfg(test)]
+    pub(crate) fn is_locked(&self) -> bool {
+        self.recv_waiting.wakers.try_lock().is_err() || self.send_waiting.wakers.try_lock().is_err()
+    }
+}
+
+impl<T> Drop for Shared<T> {
+    fn drop(&mut self) {
+        // everyone is gone, drop whatever was sent but never received and free the blocks
+        let mut head = *self.head.index.get_mut();
+        let tail = *self.tail.index.get_mut();
+        let mut block = *self.head.block.get_mut();
+        while head != tail {
+            let offset = head % LAP;
+            // SAFETY: every index between head and tail was claimed by a sender that has
+            // since finished (there are no senders left), so those slots hold values. The
+            // blocks before the head one were freed by their readers.
+            unsafe {
+                if offset < BLOCK_CAP {
+                    (*(*block).slots[offset].value.get()).assume_init_drop();
+                } else {
+                    let next = (*block).next.load(Ordering::Relaxed);
+                    drop(Box::from_raw(block));
+                    block = next;
+                }
+            }
+            head = head.wrapping_add(1);
+        }
+        // SAFETY: the current block was made with Box::into_raw and nothing else frees it
+        drop(unsafe { Box::from_raw(block) });
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.shared.senders.fetch_add(1, Ordering::Relaxed);
+        Sender {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        // AcqRel so a receiver seeing 0 also sees every value we sent
+        if self.shared.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.shared.recv_waiting.wake();
+        }
+    }
+}
+
+impl<T> Sender<T> {
+    // hands the value back if there are no receivers left to ever get it
+    pub fn send(&mut self, val: T) -> Result<(), SendError<T>> {
+        let mut val = Some(val);
+        let mut ticket = None;
+        // fast path, only bother with a waker if we have to wait
+        if let Poll::Ready(res) = self.shared.try_send(&mut val, &mut ticket) {
+            return res;
+        }
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut id = None;
+        let res = loop {
+            if let Poll::Ready(res) = self.poll_send(&mut val, &mut ticket, &mut id, &waker) {
+                break res;
+            }
+            self.shared.waits.fetch_add(1, Ordering::Relaxed);
+            // park can wake up spuriously, just like a condvar, so go round and check again
+            thread::park();
+        };
+        self.shared.send_waiting.unregister(id);
+        res
+    }
+
+    // send_async, and the waiting in send: registered first, then tried again, so anything that
+    // happens after the try wakes us
+    pub(crate) fn poll_send(
+        &self,
+        val: &mut Option<T>,
+        ticket: &mut Option<usize>,
+        id: &mut Option<usize>,
+        waker: &Waker,
+    ) -> Poll<Result<(), SendError<T>>> {
+        if let Poll::Ready(res) = self.shared.try_send(val, ticket) {
+            return Poll::Ready(res);
+        }
+        self.shared.send_waiting.register(id, waker);
+        self.shared.try_send(val, ticket)
+    }
+
+    // for Select: would send go through (or fail) right now
+    pub(crate) fn send_ready(&self) -> bool {
+        let shared = &self.shared;
+        match shared.capacity {
+            _ if shared.receivers.load(Ordering::Acquire) == 0 => true,
+            None => true,
+            Some(capacity) => shared.queued.load(Ordering::Relaxed) < capacity.max(1),
+        }
+    }
+}
+
+// mpmc: same as the mutex version, each value goes to exactly one of the receivers
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        self.shared.receivers.fetch_add(1, Ordering::Relaxed);
+        Receiver {
+            shared: Arc::clone(&self.shared),
+            _not_sync: PhantomData,
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        if self.shared.receivers.fetch_sub(1, Ordering::AcqRel) != 1 {
+            return;
+        }
+        // nobody is ever going to receive these, so drop them now rather than whenever the last
+        // sender goes away. not for rendezvous though, the blocked sender wants its value back.
+        // anything sent while we're at it goes with Shared
+        if self.shared.capacity != Some(0) {
+            while let Some(t) = self.shared.pop() {
+                drop(t);
+            }
+        }
+        // every blocked sender has to wake up and find out
+        self.shared.send_waiting.wake();
+    }
+}
+
+impl<T> Receiver<T> {
+    // never blocks, tells you whether it's worth trying again later
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        // senders push before they leave, so check for them first: if they're all gone already
+        // and there's nothing there, nothing is ever coming
+        let disconnected = self.shared.senders.load(Ordering::Acquire) == 0;
+        match self.shared.pop() {
+            Some(t) => {
+                self.shared.received_one();
+                Ok(t)
+            }
+            None if disconnected => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    pub(crate) fn recv_until(&self, deadline: Option<Instant>) -> Result<T, RecvTimeoutError> {
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut id = None;
+        let res = loop {
+            match self.poll_recv(&mut id, &waker) {
+                Poll::Ready(Ok(t)) => break Ok(t),
+                Poll::Ready(Err(RecvError)) => break Err(RecvTimeoutError::Disconnected),
+                Poll::Pending => {}
+            }
+            // park can wake up spuriously, just like a condvar, so go round and check again
+            match deadline {
+                None => thread::park(),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        break Err(RecvTimeoutError::Timeout);
+                    }
+                    thread::park_timeout(deadline - now);
+                }
+            }
+            self.shared.waits.fetch_add(1, Ordering::Relaxed);
+        };
+        self.shared.recv_waiting.unregister(id);
+        res
+    }
+
+    // recv_async, and the waiting in recv: same as poll_send
+    pub(crate) fn poll_recv(
+        &self,
+        id: &mut Option<usize>,
+        waker: &Waker,
+    ) -> Poll<Result<T, RecvError>> {
+        let mut registered = false;
+        loop {
+            match self.try_recv() {
+                Ok(t) => return Poll::Ready(Ok(t)),
+                Err(TryRecvError::Disconnected) => return Poll::Ready(Err(RecvError)),
+                Err(TryRecvError::Empty) if registered => return Poll::Pending,
+                Err(TryRecvError::Empty) => {}
+            }
+            self.shared.recv_waiting.register(id, waker);
+            registered = true;
+        }
+    }
+
+    // for Select: would recv go through (or fail) right now
+    pub(crate) fn recv_ready(&self) -> bool {
+        !self.shared.is_empty() || self.shared.senders.load(Ordering::Acquire) == 0
+    }
+}
+
+pub(crate) fn channel_with_capacity<T>(capacity: Option<usize>) -> (Sender<T>, Receiver<T>) {
+    let block = Box::into_raw(Block::new());
+    let shared = Arc::new(Shared {
+        head: Position {
+            index: AtomicUsize::new(0),
+            block: AtomicPtr::new(block),
+        },
+        tail: Position {
+            index: AtomicUsize::new(0),
+            block: AtomicPtr::new(block),
+        },
+        capacity,
+        queued: AtomicUsize::new(0),
+        senders: AtomicUsize::new(1),
+        receivers: AtomicUsize::new(1),
+        recv_waiting: Waiting::new(),
+        send_waiting: Waiting::new(),
+        sent: AtomicUsize::new(0),
+        received: AtomicUsize::new(0),
+        peak_len: AtomicUsize::new(0),
+        waits: AtomicUsize::new(0),
+    });
+    (
+        Sender {
+            shared: Arc::clone(&shared),
+        },
+        Receiver {
+            shared,
+            _not_sync: PhantomData,
+        },
+    )
+}
+
+// The rest of the channel's tests (lib.rs, select.rs, ..) run against this too with
+// `cargo test --features lockfree`, these are the block list's own corners.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::_channel;
+
+    #[test]
+    fn across_blocks() {
+        // enough to go through a bunch of blocks, in order
+        let (mut tx, rx) = _channel();
+        for i in 0..(BLOCK_CAP * 5 + 3) {
+            tx.send(i).unwrap();
+        }
+        drop(tx);
+        assert_eq!(
+            rx.iter().collect::<Vec<_>>(),
+            (0..(BLOCK_CAP * 5 + 3)).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn many_senders_and_receivers() {
+        // blocks get freed by whichever receiver is last out of them, in any order
+        let (tx, rx) = _channel();
+        let receivers: Vec<_> = (0..4)
+            .map(|_| {
+                let rx = rx.clone();
+                thread::spawn(move || rx.into_iter().collect::<Vec<_>>())
+            })
+            .collect();
+        drop(rx);
+        let senders: Vec<_> = (0..8)
+            .map(|i| {
+                let mut tx = tx.clone();
+                thread::spawn(move || {
+                    for j in 0..1000 {
+                        tx.send(i * 1000 + j).unwrap();
+                    }
+                })
+            })
+            .collect();
+        drop(tx);
+        for s in senders {
+            s.join().unwrap();
+        }
+        let mut got: Vec<_> = receivers
+            .into_iter()
+            .flat_map(|r| r.join().unwrap())
+            .collect();
+        got.sort_unstable();
+        assert_eq!(got, (0..8000).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn unreceived_values_are_dropped() {
+        let val = Arc::new(());
+        let (mut tx, rx) = _channel();
+        for _ in 0..(BLOCK_CAP * 2 + 5) {
+            tx.send(Arc::clone(&val)).unwrap();
+        }
+        // take a few so head and tail are in different blocks
+        for _ in 0..(BLOCK_CAP + 2) {
+            rx.recv().unwrap();
+        }
+        drop(rx);
+        // and one the receiver never got to see, it goes with the channel
+        assert!(tx.send(Arc::clone(&val)).is_err());
+        drop(tx);
+        assert_eq!(Arc::strong_count(&val), 1);
+    }
+
+    #[test]
+    fn rendezvous_across_blocks() {
+        // the sender pops its own value back out when the receivers go, at a block boundary too
+        let (mut tx, rx) = crate::sync_channel(0);
+        let t = thread::spawn(move || {
+            for i in 0..BLOCK_CAP {
+                tx.send(i).unwrap();
+            }
+            tx.send(BLOCK_CAP)
+        });
+        for i in 0..BLOCK_CAP {
+            assert_eq!(rx.recv(), Ok(i));
+        }
+        while rx.shared.is_empty() {
+            thread::yield_now();
+        }
+        drop(rx);
+        assert_eq!(t.join().unwrap(), Err(SendError(BLOCK_CAP)));
+    }
+}
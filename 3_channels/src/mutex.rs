@@ -0,0 +1,499 @@
+use crate::select::Wakers;
+use crate::{Receiver, RecvError, RecvTimeoutError, SendError, Sender, Stats, TryRecvError};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::task::{Poll, Waker};
+use std::time::{Duration, Instant};
+
+// The Mutex + Condvar flavor, what Sender and Receiver are without the `lockfree` feature:
+// a VecDeque behind a lock, and whoever has to wait for the other side sleeps on a condvar.
+
+// need to implement manually so that the Clone trait bound is not there
+// we don't need it since we are using an Arc
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        let mut inner = self.shared.lock();
+        inner.senders += 1;
+        drop(inner);
+
+        Sender {
+            // use Arc::clone() to explicitly say you want to clone the Arc and not the thing inside of it,
+            // the dot operator deref coercion would call wrong clone method
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut inner = self.shared.lock();
+        inner.senders -= 1;
+        let was_last = inner.senders == 0;
+        if was_last {
+            inner.wake_receivers();
+        }
+        drop(inner);
+        if was_last {
+            // every receiver blocked in recv needs to find out, not just one of them
+            self.shared.available.notify_all();
+        }
+    }
+}
+
+impl<T> Sender<T> {
+    // hands the value back if there are no receivers left to ever get it
+    pub fn send(&mut self, val: T) -> Result<(), SendError<T>> {
+        let mut inner = self.shared.lock();
+        if let Some(capacity) = inner.capacity {
+            // bounded: block until the receiver makes room
+            // same as recv, we can be woken up spuriously so re-check in a loop
+            // rendezvous still needs one slot to hand the value over through
+            while inner.receivers > 0 && inner.queue.len() >= capacity.max(1) {
+                inner = inner.wait(&self.shared.space, None);
+            }
+        }
+        if inner.receivers == 0 {
+            return Err(SendError(val));
+        }
+        inner.push(val, &self.shared.received);
+        // the value we just pushed is the `ticket`th one the receiver will take
+        let ticket = inner.taken + inner.queue.len();
+        if inner.capacity == Some(0) {
+            self.shared.available.notify_one();
+            // rendezvous: don't return until the receiver has actually taken our value
+            while inner.taken < ticket {
+                if inner.receivers == 0 {
+                    // the receivers left without taking it, and a rendezvous queue only ever
+                    // holds one value, so the one in there is ours
+                    let val = inner.queue.pop_front().expect("our value is still queued");
+                    return Err(SendError(val));
+                }
+                inner = inner.wait(&self.shared.space, None);
+            }
+            return Ok(());
+        }
+        drop(inner); // drop lock so the thing being notified can wake up and use it right away
+                     // Notify that it's time to wake up as there is work to be done
+        self.shared.available.notify_one(); // doesn't notify specific threads, can be any thread
+        Ok(())
+    }
+}
+
+// mpmc: cloning gives another consumer pulling from the same queue, each value goes to
+// exactly one of them. Same as Sender, no T: Clone needed.
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        let mut inner = self.shared.lock();
+        // from now on `pop` stops stealing the whole queue, otherwise one receiver could sit on
+        // a batch while the others go hungry. whatever we already stole stays in our buffer,
+        // it's still only received once
+        inner.receivers += 1;
+        drop(inner);
+
+        Receiver {
+            shared: Arc::clone(&self.shared),
+            buffer: RefCell::default(),
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let mut inner = self.shared.lock();
+        inner.receivers -= 1;
+        if inner.receivers > 0 {
+            // someone else can still receive what we stole, hand it back in front of anything
+            // sent since so the order is kept
+            let buffer = self.buffer.get_mut();
+            if !buffer.is_empty() {
+                inner.taken -= buffer.len();
+                buffer.append(&mut inner.queue);
+                std::mem::swap(buffer, &mut inner.queue);
+                inner.wake_receivers();
+                drop(inner);
+                self.shared.available.notify_all();
+            }
+            return;
+        }
+        // nobody is ever going to receive these, so drop them now rather than whenever the last
+        // sender goes away. not for rendezvous though, the blocked sender wants its value back
+        let unreceived = if inner.capacity == Some(0) {
+            VecDeque::new()
+        } else {
+            std::mem::take(&mut inner.queue)
+        };
+        inner.wake_senders();
+        drop(inner);
+        // every blocked sender has to wake up and find out
+        self.shared.space.notify_all();
+        // drop outside the lock, T's Drop could take a while (or panic)
+        drop(unreceived);
+    }
+}
+
+impl<T> Receiver<T> {
+    // never blocks, tells you whether it's worth trying again later
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        let mut buffer = self.buffer.borrow_mut();
+        if let Some(t) = buffer.pop_front() {
+            self.shared.received.fetch_add(1, Ordering::Relaxed);
+            return Ok(t);
+        }
+
+        let mut inner = self.shared.lock();
+        match Self::pop(&mut inner, &mut buffer) {
+            Some(t) => {
+                self.shared.received.fetch_add(1, Ordering::Relaxed);
+                let capacity = inner.capacity;
+                drop(inner);
+                self.wake_senders(capacity);
+                Ok(t)
+            }
+            None if inner.senders == 0 => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    pub(crate) fn recv_until(&self, deadline: Option<Instant>) -> Result<T, RecvTimeoutError> {
+        let mut buffer = self.buffer.borrow_mut();
+        if let Some(t) = buffer.pop_front() {
+            self.shared.received.fetch_add(1, Ordering::Relaxed);
+            return Ok(t);
+        }
+
+        let mut inner = self.shared.lock();
+        loop {
+            if let Some(t) = Self::pop(&mut inner, &mut buffer) {
+                self.shared.received.fetch_add(1, Ordering::Relaxed);
+                let capacity = inner.capacity;
+                drop(inner);
+                self.wake_senders(capacity);
+                return Ok(t);
+            }
+            // if Arc::strong_count(&self.shared) == 1 => return None, // make sure the arc rc is 1 meaning there's no senders (since the 1 is the recveiver)
+            if inner.senders == 0 {
+                return Err(RecvTimeoutError::Disconnected);
+            }
+            // thread goes to sleep until condvar wakes it up
+            // OS doesn't guarentee you aren't woken up with no work
+            // can be woken up for no reason
+            inner = match deadline {
+                None => inner.wait(&self.shared.available, None),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Err(RecvTimeoutError::Timeout);
+                    }
+                    inner.wait(&self.shared.available, Some(deadline - now))
+                }
+            };
+        }
+    }
+
+    // take one value off the shared queue, the caller holds the lock
+    fn pop(inner: &mut Inner<T>, buffer: &mut VecDeque<T>) -> Option<T> {
+        let t = inner.queue.pop_front()?;
+        inner.taken += 1;
+        if inner.capacity.is_some() {
+            inner.wake_senders();
+        }
+        // optimization to not need to take the lock to receive every send one by one
+        // can't steal the whole queue if bounded, senders would see an empty queue
+        // and could fill it again, doubling the capacity
+        // and not with more than one receiver, the others would be left waiting on an empty
+        // queue while we sit on everything
+        if inner.capacity.is_none() && inner.receivers == 1 && !inner.queue.is_empty() {
+            std::mem::swap(buffer, &mut inner.queue); // take everything that has been sent at once
+            inner.taken += buffer.len();
+        }
+        Some(t)
+    }
+
+    fn wake_senders(&self, capacity: Option<usize>) {
+        match capacity {
+            None => {}
+            // rendezvous: the sender we took from is waiting on `space` too, as are
+            // any senders waiting for the slot, so they all need to re-check
+            Some(0) => self.shared.space.notify_all(),
+            // we made room for exactly one value, so wake one blocked sender
+            Some(_) => self.shared.space.notify_one(),
+        }
+    }
+
+    // recv_async: the same as recv, except that instead of sleeping it leaves the task's waker
+    pub(crate) fn poll_recv(
+        &self,
+        id: &mut Option<usize>,
+        waker: &Waker,
+    ) -> Poll<Result<T, RecvError>> {
+        loop {
+            match self.try_recv() {
+                Ok(t) => return Poll::Ready(Ok(t)),
+                Err(TryRecvError::Disconnected) => return Poll::Ready(Err(RecvError)),
+                Err(TryRecvError::Empty) => {}
+            }
+
+            let mut inner = self.shared.lock();
+            // a send could have snuck in between try_recv and taking the lock again,
+            // only go to sleep if there's still nothing, otherwise nobody would wake us for it
+            if inner.queue.is_empty() && inner.senders > 0 {
+                inner.recv_wakers.register(id, waker);
+                return Poll::Pending;
+            }
+        }
+    }
+
+    // for Select: would recv go through (or fail) right now
+    pub(crate) fn recv_ready(&self) -> bool {
+        if !self.buffer.borrow().is_empty() {
+            return true;
+        }
+        let inner = self.shared.lock();
+        !inner.queue.is_empty() || inner.senders == 0
+    }
+}
+
+impl<T> Sender<T> {
+    // send_async: val is taken once it's pushed, ticket is set while a rendezvous send waits
+    // for its value to be taken
+    pub(crate) fn poll_send(
+        &self,
+        val: &mut Option<T>,
+        ticket: &mut Option<usize>,
+        id: &mut Option<usize>,
+        waker: &Waker,
+    ) -> Poll<Result<(), SendError<T>>> {
+        let shared = &self.shared;
+        let mut inner = shared.lock();
+
+        if let Some(t) = *ticket {
+            // same as the end of the blocking send
+            if inner.taken >= t {
+                return Poll::Ready(Ok(()));
+            }
+            if inner.receivers == 0 {
+                // the receivers left without taking it, and a rendezvous queue only ever
+                // holds one value, so the one in there is ours
+                let val = inner.queue.pop_front().expect("our value is still queued");
+                *ticket = None;
+                return Poll::Ready(Err(SendError(val)));
+            }
+        } else {
+            if inner.receivers == 0 {
+                let val = val.take().expect("SendFuture polled after completion");
+                return Poll::Ready(Err(SendError(val)));
+            }
+            let full = match inner.capacity {
+                Some(capacity) => inner.queue.len() >= capacity.max(1),
+                None => false,
+            };
+            if !full {
+                let val = val.take().expect("SendFuture polled after completion");
+                inner.push(val, &shared.received);
+                let t = inner.taken + inner.queue.len();
+                if inner.capacity != Some(0) {
+                    drop(inner);
+                    shared.available.notify_one();
+                    return Poll::Ready(Ok(()));
+                }
+                // rendezvous: not done until the receiver has it, wait for that below
+                shared.available.notify_one();
+                *ticket = Some(t);
+            }
+        }
+
+        inner.send_wakers.register(id, waker);
+        Poll::Pending
+    }
+
+    // for Select: would send go through (or fail) right now
+    pub(crate) fn send_ready(&self) -> bool {
+        let inner = self.shared.lock();
+        match inner.capacity {
+            _ if inner.receivers == 0 => true,
+            None => true,
+            Some(capacity) => inner.queue.len() < capacity.max(1),
+        }
+    }
+}
+
+struct Inner<T> {
+    queue: VecDeque<T>,
+    senders: usize,
+    capacity: Option<usize>, // None means unbounded, Some(0) is a rendezvous channel
+    taken: usize,            // how many values the receiver has pulled off `queue` so far
+    receivers: usize,        // once this hits 0 sends can never succeed again
+    // Select and futures don't sit on a condvar (Select can't wait on more than one, futures
+    // mustn't block the thread), they leave a Waker here
+    recv_wakers: Wakers, // woken when there's something to receive, or the senders are gone
+    send_wakers: Wakers, // woken when a bounded queue has room (or a value got taken), or the receivers are gone
+    woken: Vec<Waker>,   // taken out of the two above, to be woken once the lock is let go of
+    // for stats()
+    sent: usize,
+    peak_len: usize,
+    waits: usize, // every time anyone went to sleep on a condvar, spurious wakeups included
+}
+
+impl<T> Inner<T> {
+    // every send (blocking or not) goes through here so the stats stay right
+    fn push(&mut self, val: T, received: &AtomicUsize) {
+        self.queue.push_back(val);
+        self.sent += 1;
+        // counts what's sitting in receivers' local buffers too
+        let len = self.sent.saturating_sub(received.load(Ordering::Relaxed));
+        self.peak_len = self.peak_len.max(len);
+        self.wake_receivers();
+    }
+
+    // these are called with the lock held, so they only collect the wakers, see Locked
+    fn wake_receivers(&mut self) {
+        self.recv_wakers.take(&mut self.woken);
+    }
+
+    fn wake_senders(&mut self) {
+        self.send_wakers.take(&mut self.woken);
+    }
+}
+
+// The lock on Inner. Wakers collected while holding it only get woken once it's let go of: an
+// executor can poll the woken future right there inside wake, on this thread, and that poll is
+// going to want the lock too.
+struct Locked<'a, T> {
+    mutex: &'a Mutex<Inner<T>>,
+    guard: Option<MutexGuard<'a, Inner<T>>>, // only None in the middle of wait and drop
+}
+
+impl<T> Shared<T> {
+    fn lock(&self) -> Locked<'_, T> {
+        Locked {
+            mutex: &self.inner,
+            guard: Some(self.inner.lock().unwrap()),
+        }
+    }
+
+    // Select and the futures leave their wakers through these
+    pub(crate) fn register_recv(&self, id: &mut Option<usize>, waker: &Waker) {
+        self.lock().recv_wakers.register(id, waker);
+    }
+
+    pub(crate) fn unregister_recv(&self, id: Option<usize>) {
+        self.lock().recv_wakers.unregister(id);
+    }
+
+    pub(crate) fn register_send(&self, id: &mut Option<usize>, waker: &Waker) {
+        self.lock().send_wakers.register(id, waker);
+    }
+
+    pub(crate) fn unregister_send(&self, id: Option<usize>) {
+        self.lock().send_wakers.unregister(id);
+    }
+
+    pub(crate) fn stats(&self) -> Stats {
+        let inner = self.inner.lock().unwrap();
+        let received = self.received.load(Ordering::Relaxed);
+        Stats {
+            len: inner.sent.saturating_sub(received),
+            peak_len: inner.peak_len,
+            senders: inner.senders,
+            receivers: inner.receivers,
+            sent: inner.sent,
+            received,
+            waits: inner.waits,
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn recv_wakers_empty(&self) -> bool {
+        self.inner.lock().unwrap().recv_wakers.is_empty()
+    }
+
+    #[cfg(test)]
+    pub(crate) fn send_wakers_empty(&self) -> bool {
+        self.inner.lock().unwrap().send_wakers.is_empty()
+    }
+
+    #[cfg(test)]
+    pub(crate) fn is_locked(&self) -> bool {
+        self.inner.try_lock().is_err()
+    }
+}
+
+impl<T> Locked<'_, T> {
+    // Condvar::wait(_timeout), except that anything waiting to be woken gets woken first. That
+    // means letting go of the lock without waiting on the condvar, so it comes back like a
+    // spurious wakeup would, which every wait loop checks for anyway. Only the condvar counts
+    // towards waits, the other way round nobody slept.
+    fn wait(mut self, condvar: &Condvar, timeout: Option<Duration>) -> Self {
+        let mut guard = self.guard.take().expect("only taken in wait and drop");
+        if !guard.woken.is_empty() {
+            let woken = std::mem::take(&mut guard.woken);
+            drop(guard);
+            woken.into_iter().for_each(Waker::wake);
+            self.guard = Some(self.mutex.lock().unwrap());
+            return self;
+        }
+        guard.waits += 1;
+        self.guard = Some(match timeout {
+            None => condvar.wait(guard).unwrap(),
+            Some(timeout) => condvar.wait_timeout(guard, timeout).unwrap().0,
+        });
+        self
+    }
+}
+
+impl<T> std::ops::Deref for Locked<'_, T> {
+    type Target = Inner<T>;
+    fn deref(&self) -> &Inner<T> {
+        self.guard.as_ref().expect("only taken in wait and drop")
+    }
+}
+
+impl<T> std::ops::DerefMut for Locked<'_, T> {
+    fn deref_mut(&mut self) -> &mut Inner<T> {
+        self.guard.as_mut().expect("only taken in wait and drop")
+    }
+}
+
+impl<T> Drop for Locked<'_, T> {
+    fn drop(&mut self) {
+        if let Some(mut guard) = self.guard.take() {
+            let woken = std::mem::take(&mut guard.woken);
+            drop(guard);
+            woken.into_iter().for_each(Waker::wake);
+        }
+    }
+}
+
+pub(crate) struct Shared<T> {
+    inner: Mutex<Inner<T>>,
+    available: Condvar,
+    space: Condvar, // senders blocked on a full (bounded) queue wait on this
+    // not in Inner because receivers bump it without the lock when they pop from their buffer
+    received: AtomicUsize,
+}
+
+pub(crate) fn channel_with_capacity<T>(capacity: Option<usize>) -> (Sender<T>, Receiver<T>) {
+    let inner = Inner {
+        queue: VecDeque::default(),
+        senders: 1,
+        capacity,
+        taken: 0,
+        receivers: 1,
+        recv_wakers: Wakers::default(),
+        send_wakers: Wakers::default(),
+        woken: Vec::new(),
+        sent: 0,
+        peak_len: 0,
+        waits: 0,
+    };
+    let shared = Shared {
+        inner: Mutex::new(inner),
+        available: Condvar::default(),
+        space: Condvar::default(),
+        received: AtomicUsize::new(0),
+    };
+    let shared = Arc::new(shared);
+    (Sender { shared: shared.clone() }, Receiver { shared: shared.clone(), buffer: RefCell::default() })
+}
@@ -0,0 +1,253 @@
+use crate::{RecvError, RecvTimeoutError, SendError, TryRecvError};
+use std::cell::UnsafeCell;
+use std::mem::MaybeUninit;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, Thread};
+use std::time::{Duration, Instant};
+
+// Oneshot: only ever one value goes through, so no queue, no lock around the value.
+// The value lives in a slot and `state` gets atomically swapped to say who owns the slot.
+// Whoever swaps the state last is the one who has to clean up after the other side.
+
+const EMPTY: u8 = 0; // nothing sent yet, the sender owns the slot
+const SENT: u8 = 1; // slot holds a value, the receiver owns the slot
+const DISCONNECTED: u8 = 2; // sender dropped without sending, or the value was already taken
+const RECEIVER_GONE: u8 = 3; // receiver dropped, nobody will ever look at the slot
+
+struct Shared<T> {
+    state: AtomicU8,
+    slot: UnsafeCell<MaybeUninit<T>>,
+    // only touched when someone actually needs to block, not on the value path
+    waiter: Mutex<Option<Thread>>,
+}
+
+// SAFETY: the slot is only ever accessed by whichever side `state` says owns it,
+// and the value moves from the sending thread to the receiving one, so T: Send is enough
+unsafe impl<T: Send> Sync for Shared<T> {}
+
+impl<T> Shared<T> {
+    fn wake(&self) {
+        if let Some(t) = self.waiter.lock().unwrap().take() {
+            t.unpark();
+        }
+    }
+}
+
+pub struct OneshotSender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> OneshotSender<T> {
+    // consumes the sender, there's no second value to send
+    pub fn send(self, val: T) -> Result<(), SendError<T>> {
+        // SAFETY: state is EMPTY or RECEIVER_GONE since we haven't sent (we're consuming self),
+        // in both cases nobody but us touches the slot
+        unsafe { (*self.shared.slot.get()).write(val) };
+        // Release so the receiver sees the value we just wrote when it sees SENT,
+        // Acquire to see the receiver's last write if it left already
+        match self.shared.state.swap(SENT, Ordering::AcqRel) {
+            EMPTY => {
+                self.shared.wake();
+                Ok(())
+            }
+            RECEIVER_GONE => {
+                // SAFETY: the receiver is gone so it never read the value, it's still ours
+                let val = unsafe { (*self.shared.slot.get()).assume_init_read() };
+                Err(SendError(val))
+            }
+            _ => unreachable!("only the sender can set SENT or DISCONNECTED before sending"),
+        }
+    }
+}
+
+impl<T> Drop for OneshotSender<T> {
+    fn drop(&mut self) {
+        // if we already sent (or the receiver is gone) this fails and there's nothing to do
+        if self
+            .shared
+            .state
+            .compare_exchange(EMPTY, DISCONNECTED, Ordering::Release, Ordering::Relaxed)
+            .is_ok()
+        {
+            self.shared.wake();
+        }
+    }
+}
+
+pub struct OneshotReceiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> OneshotReceiver<T> {
+    // Disconnected both when the sender went away without sending and when the value has been
+    // received already: either way nothing is ever coming. Telling the two apart is up to the
+    // caller, who is the one that got the Ok.
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        match self.shared.state.load(Ordering::Acquire) {
+            EMPTY => Err(TryRecvError::Empty),
+            SENT => {
+                // SAFETY: SENT means the sender wrote the value and handed the slot to us
+                // (the Acquire load pairs with the Release swap in send)
+                let val = unsafe { (*self.shared.slot.get()).assume_init_read() };
+                // nobody else changes the state once it's SENT, so a plain store is fine.
+                // the slot is empty again, so drop mustn't read it a second time
+                self.shared.state.store(DISCONNECTED, Ordering::Relaxed);
+                Ok(val)
+            }
+            DISCONNECTED => Err(TryRecvError::Disconnected),
+            _ => unreachable!("only the receiver sets RECEIVER_GONE, and it's still here"),
+        }
+    }
+
+    pub fn recv(mut self) -> Result<T, RecvError> {
+        self.register();
+        loop {
+            match self.try_recv() {
+                Ok(t) => return Ok(t),
+                Err(TryRecvError::Disconnected) => return Err(RecvError),
+                // park can wake up spuriously, just like a condvar, so check again
+                Err(TryRecvError::Empty) => thread::park(),
+            }
+        }
+    }
+
+    pub fn recv_timeout(&mut self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        let deadline = Instant::now() + timeout;
+        self.register();
+        loop {
+            match self.try_recv() {
+                Ok(t) => return Ok(t),
+                Err(TryRecvError::Disconnected) => return Err(RecvTimeoutError::Disconnected),
+                Err(TryRecvError::Empty) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        // we're not waiting anymore, a send shouldn't go unparking us later on
+                        self.unregister();
+                        return Err(RecvTimeoutError::Timeout);
+                    }
+                    thread::park_timeout(deadline - now);
+                }
+            }
+        }
+    }
+
+    // has to happen before we check the state the first time, otherwise the sender could
+    // send + look for a waiter between our check and our park, and we'd sleep forever
+    fn register(&self) {
+        *self.shared.waiter.lock().unwrap() = Some(thread::current());
+    }
+
+    fn unregister(&self) {
+        *self.shared.waiter.lock().unwrap() = None;
+    }
+}
+
+impl<T> Drop for OneshotReceiver<T> {
+    fn drop(&mut self) {
+        // Acquire so that if a value is in there, we see it before dropping it
+        if self.shared.state.swap(RECEIVER_GONE, Ordering::Acquire) == SENT {
+            // SAFETY: SENT means the slot holds a value nobody has taken yet
+            unsafe { (*self.shared.slot.get()).assume_init_drop() };
+        }
+    }
+}
+
+pub fn oneshot<T>() -> (OneshotSender<T>, OneshotReceiver<T>) {
+    let shared = Arc::new(Shared {
+        state: AtomicU8::new(EMPTY),
+        slot: UnsafeCell::new(MaybeUninit::uninit()),
+        waiter: Mutex::new(None),
+    });
+    (
+        OneshotSender {
+            shared: Arc::clone(&shared),
+        },
+        OneshotReceiver { shared },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ping_pong() {
+        let (tx, rx) = oneshot();
+        tx.send(42).unwrap();
+        assert_eq!(rx.recv(), Ok(42));
+    }
+
+    #[test]
+    fn across_threads() {
+        let (tx, rx) = oneshot();
+        let t = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            tx.send(String::from("reply")).unwrap();
+        });
+        assert_eq!(rx.recv().as_deref(), Ok("reply"));
+        t.join().unwrap();
+    }
+
+    #[test]
+    fn closed_tx() {
+        let (tx, rx) = oneshot::<()>();
+        drop(tx);
+        assert_eq!(rx.recv(), Err(RecvError));
+    }
+
+    #[test]
+    fn closed_tx_while_blocked() {
+        let (tx, rx) = oneshot::<()>();
+        let t = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            drop(tx);
+        });
+        assert_eq!(rx.recv(), Err(RecvError));
+        t.join().unwrap();
+    }
+
+    #[test]
+    fn closed_rx() {
+        let (tx, rx) = oneshot();
+        drop(rx);
+        assert_eq!(tx.send(42), Err(SendError(42)));
+    }
+
+    #[test]
+    fn try_recv() {
+        let (tx, mut rx) = oneshot();
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+        tx.send(1).unwrap();
+        assert_eq!(rx.try_recv(), Ok(1));
+        // the one value has been taken, there's never going to be another
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+
+        // which looks the same as a sender that never sent
+        let (tx, mut rx) = oneshot::<()>();
+        drop(tx);
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+    }
+
+    #[test]
+    fn recv_timeout() {
+        let (tx, mut rx) = oneshot();
+        assert_eq!(
+            rx.recv_timeout(Duration::from_millis(10)),
+            Err(RecvTimeoutError::Timeout)
+        );
+        // gave up waiting, so the send mustn't unpark us
+        assert!(rx.shared.waiter.lock().unwrap().is_none());
+        tx.send(1).unwrap();
+        assert_eq!(rx.recv_timeout(Duration::from_millis(10)), Ok(1));
+    }
+
+    #[test]
+    fn unreceived_value_is_dropped() {
+        let val = Arc::new(());
+        let (tx, rx) = oneshot();
+        tx.send(Arc::clone(&val)).unwrap();
+        drop(rx);
+        assert_eq!(Arc::strong_count(&val), 1);
+    }
+}
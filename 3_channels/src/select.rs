@@ -0,0 +1,454 @@
+use crate::{ReadyTimeoutError, Receiver, Sender, TryReadyError};
+use std::sync::Arc;
+use std::task::{Wake, Waker};
+use std::thread::{self, Thread};
+use std::time::{Duration, Instant};
+
+// Waiting on several channels at once.
+// Can't wait on more than one condvar, so instead the selecting thread leaves a Waker in every
+// channel it's interested in, and parks. Any of them waking it up means "go check again".
+//
+//     let mut sel = Select::new();
+//     let a = sel.recv(&rx1);
+//     let b = sel.recv(&rx2);
+//     match sel.ready() {
+//         i if i == a => { let msg = rx1.try_recv(); .. }
+//         i if i == b => { let msg = rx2.try_recv(); .. }
+//         _ => unreachable!(),
+//     }
+//
+// ready only says the operation *would* go through right now. With other receivers (or senders)
+// on the same channel someone else can get there first, so use try_recv after and be ready for
+// Empty. A send on a rendezvous channel is "ready" when its slot is free, the send itself still
+// waits for a receiver to take the value.
+
+pub struct Select<'a> {
+    handles: Vec<&'a dyn Handle>,
+    // start looking from a different operation each time, so the first one can't starve the rest
+    next: usize,
+}
+
+// something Select can wait on, kept private so it's just our Sender/Receiver
+pub(crate) trait Handle {
+    fn is_ready(&self) -> bool;
+    fn register(&self, id: &mut Option<usize>, waker: &Waker);
+    fn unregister(&self, id: Option<usize>);
+}
+
+impl<'a> Select<'a> {
+    pub fn new() -> Self {
+        Select {
+            handles: Vec::new(),
+            next: 0,
+        }
+    }
+
+    // ready once there's a value to receive, or all the senders are gone (recv won't block either way)
+    // returns the index ready will hand back when this one fires
+    pub fn recv<T>(&mut self, rx: &'a Receiver<T>) -> usize {
+        self.handles.push(rx);
+        self.handles.len() - 1
+    }
+
+    // ready once there's room in the queue, or all the receivers are gone
+    pub fn send<T>(&mut self, tx: &'a Sender<T>) -> usize {
+        self.handles.push(tx);
+        self.handles.len() - 1
+    }
+
+    // the `default` arm: don't block at all
+    pub fn try_ready(&mut self) -> Result<usize, TryReadyError> {
+        self.poll().ok_or(TryReadyError)
+    }
+
+    // panics with nothing added, there'd be nothing to ever wake it up
+    pub fn ready(&mut self) -> usize {
+        assert!(!self.handles.is_empty(), "ready on an empty Select");
+        self.ready_until(None).expect("no deadline to time out on")
+    }
+
+    pub fn ready_timeout(&mut self, timeout: Duration) -> Result<usize, ReadyTimeoutError> {
+        self.ready_deadline(Instant::now() + timeout)
+    }
+
+    pub fn ready_deadline(&mut self, deadline: Instant) -> Result<usize, ReadyTimeoutError> {
+        self.ready_until(Some(deadline)).ok_or(ReadyTimeoutError)
+    }
+
+    fn ready_until(&mut self, deadline: Option<Instant>) -> Option<usize> {
+        // fast path, don't bother with wakers if something is already there
+        if let Some(i) = self.poll() {
+            return Some(i);
+        }
+
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        // our registration in each channel
+        let mut ids = vec![None; self.handles.len()];
+        let ready = loop {
+            // Waking takes the wakers out of the channel, so after every wakeup ours is gone from
+            // the one that woke us. If someone else (another receiver, another sender) got there
+            // first, we'd park again with nothing left to wake us, so register every time round.
+            // Registered first, then checked, so anything that happens after this check wakes us.
+            for (handle, id) in self.handles.iter().zip(&mut ids) {
+                handle.register(id, &waker);
+            }
+            if let Some(i) = self.poll() {
+                break Some(i);
+            }
+            // park can wake up spuriously, just like a condvar, so go round and check again
+            match deadline {
+                None => thread::park(),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        break None;
+                    }
+                    thread::park_timeout(deadline - now);
+                }
+            }
+        };
+        // the channels we didn't pick still have our waker, don't leave it lying around
+        for (handle, id) in self.handles.iter().zip(ids) {
+            handle.unregister(id);
+        }
+        ready
+    }
+
+    fn poll(&mut self) -> Option<usize> {
+        let n = self.handles.len();
+        let start = self.next;
+        let i = (0..n)
+            .map(|i| (start + i) % n)
+            .find(|&i| self.handles[i].is_ready())?;
+        self.next = (i + 1) % n;
+        Some(i)
+    }
+}
+
+impl Default for Select<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// wakes a thread parked in Select, also handy for driving futures on a plain thread
+pub(crate) struct ThreadWaker(pub(crate) Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+// The wakers waiting on one side of a channel. Every registration (a future, a Select) gets an id
+// of its own to find it by again, rather than going by the Waker: two futures in the same task
+// (say in one join!) have the same Waker, and one of them going away mustn't take the other's out.
+#[derive(Default)]
+pub(crate) struct Wakers {
+    next_id: usize,
+    wakers: Vec<(usize, Waker)>,
+}
+
+impl Wakers {
+    // Puts waker in under id, or in place of whatever is there under it already (the future may
+    // have moved to another task since). The first time round id is None, and gets one here.
+    pub(crate) fn register(&mut self, id: &mut Option<usize>, waker: &Waker) {
+        let next_id = &mut self.next_id;
+        let id = *id.get_or_insert_with(|| {
+            *next_id += 1;
+            *next_id
+        });
+        match self.wakers.iter_mut().find(|(i, _)| *i == id) {
+            Some((_, w)) if w.will_wake(waker) => {}
+            Some((_, w)) => *w = waker.clone(),
+            // new, or it was woken (and taken out) since
+            None => self.wakers.push((id, waker.clone())),
+        }
+    }
+
+    pub(crate) fn unregister(&mut self, id: Option<usize>) {
+        if let Some(id) = id {
+            self.wakers.retain(|(i, _)| *i != id);
+        }
+    }
+
+    // takes them all out, for waking once the lock is let go of
+    pub(crate) fn take(&mut self, into: &mut Vec<Waker>) {
+        into.extend(self.wakers.drain(..).map(|(_, w)| w));
+    }
+
+    #[cfg(test)]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.wakers.is_empty()
+    }
+}
+
+impl<T> Handle for Receiver<T> {
+    fn is_ready(&self) -> bool {
+        self.recv_ready()
+    }
+
+    fn register(&self, id: &mut Option<usize>, waker: &Waker) {
+        self.shared.register_recv(id, waker);
+    }
+
+    fn unregister(&self, id: Option<usize>) {
+        self.shared.unregister_recv(id);
+    }
+}
+
+impl<T> Handle for Sender<T> {
+    fn is_ready(&self) -> bool {
+        self.send_ready()
+    }
+
+    fn register(&self, id: &mut Option<usize>, waker: &Waker) {
+        self.shared.register_send(id, waker);
+    }
+
+    fn unregister(&self, id: Option<usize>) {
+        self.shared.unregister_send(id);
+    }
+}
+
+// The same thing as a macro, for receivers, in the shape of crossbeam's select!:
+//
+//     select! {
+//         recv(rx1) -> msg => println!("rx1: {:?}", msg),
+//         recv(rx2) -> msg => println!("rx2: {:?}", msg),
+//         default(Duration::from_secs(1)) => println!("nothing for a second"),
+//     }
+//
+// msg is what recv would have returned (Err once the senders are gone), so the pattern has to be
+// one that can't fail, like a name. `default => ..` runs if nothing is ready right now, and without
+// a default it waits for as long as it takes. Receivers get evaluated more than once, so pass
+// something like `rx` or `&self.rx`, not a call that makes a new one.
+// It loops over Select and try_recv: if another receiver takes the value first, it waits again.
+// Sends aren't in here, use Select::send and then send.
+#[macro_export]
+macro_rules! select {
+    // gives every arm a variable for its value and one for its index in the Select, made here so
+    // each arm's are separate (every expansion gets its own `v` and `idx`)
+    (@arms [$($done:tt)*] [($rx:expr, $res:pat, $body:expr) $($rest:tt)*] $mode:tt $default:expr) => {
+        $crate::select!(@arms [$($done)* (v, idx, $rx, $res, $body)] [$($rest)*] $mode $default)
+    };
+    (@arms [$($done:tt)*] [] $mode:tt $default:expr) => {
+        $crate::select!(@go [$($done)*] $mode $default)
+    };
+
+    (@deadline $deadline:ident [timeout $timeout:expr]) => {
+        let $deadline = ::std::time::Instant::now() + $timeout;
+    };
+    (@deadline $deadline:ident [$($mode:tt)*]) => {};
+    (@wait $sel:ident $deadline:ident [try]) => { $sel.try_ready().ok() };
+    (@wait $sel:ident $deadline:ident [timeout $timeout:expr]) => { $sel.ready_deadline($deadline).ok() };
+    (@wait $sel:ident $deadline:ident [block]) => { ::std::option::Option::Some($sel.ready()) };
+
+    (@go [$(($v:ident, $idx:ident, $rx:expr, $res:pat, $body:expr))+] $mode:tt $default:expr) => {{
+        $crate::select!(@deadline deadline $mode);
+        $( let mut $v = ::std::option::Option::None; )+
+        let mut sel = $crate::Select::new();
+        $( let $idx = sel.recv(&$rx); )+
+        loop {
+            let i = match $crate::select!(@wait sel deadline $mode) {
+                ::std::option::Option::Some(i) => i,
+                ::std::option::Option::None => break,
+            };
+            $(
+                if i == $idx {
+                    match $rx.try_recv() {
+                        ::std::result::Result::Ok(t) => {
+                            $v = ::std::option::Option::Some(::std::result::Result::Ok(t));
+                            break;
+                        }
+                        ::std::result::Result::Err($crate::TryRecvError::Disconnected) => {
+                            $v = ::std::option::Option::Some(::std::result::Result::Err($crate::RecvError));
+                            break;
+                        }
+                        // someone else got there first
+                        ::std::result::Result::Err($crate::TryRecvError::Empty) => continue,
+                    }
+                }
+            )+
+        }
+        drop(sel);
+        // outside the loop, so a break or continue in an arm is about the caller's loop
+        $( if let ::std::option::Option::Some($res) = $v { $body } else )+ { $default }
+    }};
+
+    ($(recv($rx:expr) -> $res:pat => $body:expr,)+ default => $default:expr $(,)?) => {
+        $crate::select!(@arms [] [$(($rx, $res, $body))+] [try] $default)
+    };
+    ($(recv($rx:expr) -> $res:pat => $body:expr,)+ default($timeout:expr) => $default:expr $(,)?) => {
+        $crate::select!(@arms [] [$(($rx, $res, $body))+] [timeout $timeout] $default)
+    };
+    ($(recv($rx:expr) -> $res:pat => $body:expr),+ $(,)?) => {
+        $crate::select!(@arms [] [$(($rx, $res, $body))+] [block] unreachable!("no default to fall back to"))
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{_channel, sync_channel, RecvError, TryRecvError};
+
+    #[test]
+    fn picks_the_ready_one() {
+        let (_tx1, rx1) = _channel::<i32>();
+        let (mut tx2, rx2) = _channel();
+        tx2.send(2).unwrap();
+        let mut sel = Select::new();
+        let _a = sel.recv(&rx1);
+        let b = sel.recv(&rx2);
+        assert_eq!(sel.ready(), b);
+        assert_eq!(rx2.try_recv(), Ok(2));
+    }
+
+    #[test]
+    fn blocks_until_ready() {
+        let (_tx1, rx1) = _channel::<i32>();
+        let (mut tx2, rx2) = _channel();
+        let t = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            tx2.send("hi").unwrap();
+        });
+        let mut sel = Select::new();
+        sel.recv(&rx1);
+        let b = sel.recv(&rx2);
+        assert_eq!(sel.ready(), b);
+        drop(sel);
+        assert_eq!(rx2.recv(), Ok("hi"));
+        t.join().unwrap();
+        // we got picked, rx1 must not be left holding our waker
+        assert!(rx1.shared.recv_wakers_empty());
+    }
+
+    #[test]
+    fn disconnect_is_ready() {
+        let (tx, rx) = _channel::<()>();
+        let mut sel = Select::new();
+        let a = sel.recv(&rx);
+        drop(tx);
+        assert_eq!(sel.ready(), a);
+        assert_eq!(rx.recv(), Err(RecvError));
+    }
+
+    #[test]
+    fn default_and_timeout() {
+        let (_tx, rx) = _channel::<()>();
+        let mut sel = Select::new();
+        sel.recv(&rx);
+        assert_eq!(sel.try_ready(), Err(TryReadyError));
+        assert_eq!(
+            sel.ready_timeout(Duration::from_millis(20)),
+            Err(ReadyTimeoutError)
+        );
+        assert!(rx.shared.recv_wakers_empty());
+    }
+
+    #[test]
+    fn bounded_send() {
+        let (mut tx, rx) = sync_channel(1);
+        tx.send(1).unwrap();
+        let t = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            assert_eq!(rx.recv(), Ok(1));
+            rx
+        });
+        let mut sel = Select::new();
+        let a = sel.send(&tx);
+        assert_eq!(sel.try_ready(), Err(TryReadyError));
+        assert_eq!(sel.ready(), a);
+        drop(sel);
+        tx.send(2).unwrap();
+        let rx = t.join().unwrap();
+        assert_eq!(rx.try_recv(), Ok(2));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+    }
+
+    #[test]
+    fn loses_a_race_to_another_receiver() {
+        let (mut tx, rx) = _channel();
+        let rx2 = rx.clone();
+        let shared = Arc::clone(&rx.shared);
+        let t = thread::spawn(move || {
+            let mut sel = Select::new();
+            let a = sel.recv(&rx);
+            let start = Instant::now();
+            let ready = sel.ready_timeout(Duration::from_secs(5));
+            (ready == Ok(a), start.elapsed(), rx.try_recv())
+        });
+        // wait for the Select to be parked, with its waker in the channel
+        while shared.recv_wakers_empty() {
+            thread::yield_now();
+        }
+        tx.send(1).unwrap();
+        // the Select was woken for this, but the other receiver takes it first (almost always,
+        // if the Select wins instead there's no race to test)
+        let stolen = rx2.try_recv() == Ok(1);
+        thread::sleep(Duration::from_millis(100));
+        tx.send(2).unwrap();
+
+        let (ready, took, got) = t.join().unwrap();
+        assert!(ready);
+        if stolen {
+            // without a waker left in the channel, this would only come back after the 5s
+            assert!(took < Duration::from_secs(2), "took {:?}", took);
+            assert_eq!(got, Ok(2));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "empty Select")]
+    fn empty() {
+        let mut sel = Select::new();
+        assert_eq!(sel.try_ready(), Err(TryReadyError));
+        sel.ready();
+    }
+
+    #[test]
+    fn takes_turns() {
+        let (mut tx1, rx1) = _channel();
+        let (mut tx2, rx2) = _channel();
+        tx1.send(1).unwrap();
+        tx2.send(2).unwrap();
+        let mut sel = Select::new();
+        let a = sel.recv(&rx1);
+        let b = sel.recv(&rx2);
+        // both are ready, the second call shouldn't just pick the first one again
+        assert_eq!(sel.ready(), a);
+        assert_eq!(sel.ready(), b);
+    }
+
+    #[test]
+    fn macro_arms() {
+        let (mut tx1, rx1) = _channel();
+        let (tx2, rx2) = _channel::<&str>();
+        tx1.send(1).unwrap();
+        let got = crate::select! {
+            recv(rx1) -> msg => msg.map(|n| n * 10),
+            recv(rx2) -> msg => msg.map(|_| 0),
+        };
+        assert_eq!(got, Ok(10));
+
+        let nothing = crate::select! {
+            recv(rx1) -> _msg => false,
+            default => true,
+        };
+        assert!(nothing);
+
+        let timed_out = crate::select! {
+            recv(rx1) -> _msg => false,
+            recv(rx2) -> _msg => false,
+            default(Duration::from_millis(20)) => true,
+        };
+        assert!(timed_out);
+
+        drop(tx2);
+        let closed = crate::select! {
+            recv(rx1) -> _msg => None,
+            recv(rx2) -> msg => Some(msg),
+        };
+        assert_eq!(closed, Some(Err(RecvError)));
+    }
+}
@@ -0,0 +1,103 @@
+use crate::{Receiver, Sender};
+
+// A snapshot of what's going on inside the channel, for spotting stalled consumers and the like.
+// Taken under the lock, but the receivers' local buffers don't go through the lock,
+// so with a receiver actively draining the numbers can be a little behind. With the
+// `lockfree` list there's no lock at all, each number is read on its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stats {
+    // sent but not received yet, including what a receiver stole into its buffer
+    pub len: usize,
+    pub peak_len: usize, // the most `len` has ever been
+    pub senders: usize,
+    pub receivers: usize,
+    pub sent: usize,
+    pub received: usize,
+    // times a sender or receiver slept on a condvar (parked, with the list), spurious wakeups included
+    pub waits: usize,
+}
+
+impl<T> Sender<T> {
+    pub fn stats(&self) -> Stats {
+        self.shared.stats()
+    }
+}
+
+impl<T> Receiver<T> {
+    pub fn stats(&self) -> Stats {
+        self.shared.stats()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{_channel, sync_channel, Receiver};
+    use std::sync::{Arc, Mutex};
+    use std::task::{Wake, Waker};
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn counts() {
+        let (mut tx, rx) = _channel();
+        let tx2 = tx.clone();
+        for i in 0..5 {
+            tx.send(i).unwrap();
+        }
+        // the first recv steals the rest into the local buffer, they still count as queued
+        assert_eq!(rx.recv(), Ok(0));
+        let stats = tx.stats();
+        assert_eq!(stats.len, 4);
+        assert_eq!(stats.peak_len, 5);
+        assert_eq!(stats.senders, 2);
+        assert_eq!(stats.receivers, 1);
+        assert_eq!(stats.sent, 5);
+        assert_eq!(stats.received, 1);
+        assert_eq!(rx.try_iter().count(), 4);
+        drop(tx2);
+        let stats = rx.stats();
+        assert_eq!(stats.len, 0);
+        assert_eq!(stats.peak_len, 5);
+        assert_eq!(stats.senders, 1);
+        assert_eq!(stats.received, 5);
+        assert_eq!(stats.waits, 0);
+    }
+
+    #[test]
+    fn waits() {
+        let (mut tx, rx) = sync_channel(1);
+        let t = thread::spawn(move || {
+            tx.send(1).unwrap();
+            // full, has to wait for the receiver
+            tx.send(2).unwrap();
+            tx
+        });
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(rx.recv(), Ok(1));
+        assert_eq!(rx.recv(), Ok(2));
+        let tx = t.join().unwrap();
+        assert!(tx.stats().waits >= 1);
+    }
+
+    // a task receiving right there inside wake, like an executor that polls inline would
+    struct Inline(Mutex<Receiver<i32>>, Mutex<Option<i32>>);
+
+    impl Wake for Inline {
+        fn wake(self: Arc<Self>) {
+            *self.1.lock().unwrap() = self.0.lock().unwrap().try_recv().ok();
+        }
+    }
+
+    #[test]
+    fn no_waits_without_sleeping() {
+        let (mut tx, rx) = sync_channel(0);
+        let shared = rx.shared.clone();
+        let inline = Arc::new(Inline(Mutex::new(rx), Mutex::new(None)));
+        let waker = Waker::from(inline.clone());
+        shared.register_recv(&mut None, &waker);
+        // the value is taken before the sender gets round to the condvar
+        tx.send(1).unwrap();
+        assert_eq!(*inline.1.lock().unwrap(), Some(1));
+        assert_eq!(tx.stats().waits, 0);
+    }
+}
@@ -1,87 +1,430 @@
-use std::cmp::Ordering;
 use rand::prelude::*;
-use std::cell::Cell;
-use std::rc::Rc;
-use std::sync::{
-    atomic::{AtomicUsize, Ordering::Relaxed},
-    Arc,
-};
+use std::cmp::Ordering;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::Instant;
 use vid5::*;
 
-#[derive(Clone)]
-struct SortEvaluator<T> {
-    t: T,
-    cmps: Rc<Cell<usize>>,
+// cargo run --release --bin bench -- [--sizes 10,100,1000] [--algos quick,tim,..] [--dists random,..]
+//                                     [--iters 10] [--format csv|json]
+//
+// Every run sorts the same input twice: once as plain u64s for the wall time, and once as
+// SortEvaluators that count what the sorter does to them, which would skew the time.
+
+// counters for the SortEvaluators, global so the parallel sorters can share them between threads
+static CMPS: AtomicUsize = AtomicUsize::new(0);
+static MOVES: AtomicUsize = AtomicUsize::new(0);
+
+// A value that counts comparisons, and moves: a sorter never tells us when it moves something, so
+// each value remembers the address it was last seen at, and whenever it gets compared (and once
+// more at the end) a new address means it was moved. Going somewhere and back in between counts
+// as nothing, so this is a lower bound, but a swap that sticks is two moves like you'd expect.
+struct SortEvaluator {
+    t: u64,
+    at: AtomicUsize,
+}
+
+impl SortEvaluator {
+    fn new(t: u64) -> Self {
+        SortEvaluator {
+            t,
+            at: AtomicUsize::new(0),
+        }
+    }
+
+    fn seen(&self) {
+        let here = self as *const Self as usize;
+        if self.at.swap(here, Relaxed) != here {
+            MOVES.fetch_add(1, Relaxed);
+        }
+    }
+
+    fn compared(&self, other: &Self) {
+        self.seen();
+        other.seen();
+        CMPS.fetch_add(1, Relaxed);
+    }
 }
 
-impl<T: PartialEq> PartialEq for SortEvaluator<T> {
+impl Clone for SortEvaluator {
+    fn clone(&self) -> Self {
+        SortEvaluator::new(self.t)
+    }
+}
+
+impl PartialEq for SortEvaluator {
     fn eq(&self, other: &Self) -> bool {
-        self.cmps.set(self.cmps.get() + 1);
+        self.compared(other);
         self.t == other.t
     }
 }
 
-impl<T: Eq> Eq for SortEvaluator<T> {}
+impl Eq for SortEvaluator {}
 
-impl<T: PartialOrd> PartialOrd for SortEvaluator<T> {
+impl PartialOrd for SortEvaluator {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.cmps.set(self.cmps.get() + 1);
-        self.t.partial_cmp(&other.t)
+        Some(self.cmp(other))
     }
 }
 
-impl<T: Ord> Ord for SortEvaluator<T> {
+impl Ord for SortEvaluator {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.cmps.set(self.cmps.get() + 1);
+        self.compared(other);
         self.t.cmp(&other.t)
     }
 }
 
+// the key sorters look at the bytes instead, which isn't a comparison
+impl RadixKey for SortEvaluator {
+    fn len(&self) -> usize {
+        self.t.len()
+    }
+
+    fn byte(&self, i: usize) -> u8 {
+        self.seen();
+        self.t.byte(i)
+    }
+}
+
 struct StdSorter;
 impl Sorter for StdSorter {
-    fn sort<T>(&self, slice: &mut [T])
+    fn sort_by<T, F>(&self, slice: &mut [T], compare: F)
     where
-        T: Ord,
+        F: FnMut(&T, &T) -> Ordering,
     {
-        slice.sort();
+        slice.sort_by(compare);
+    }
+}
+
+// QuickSort from before the sorting networks, as the baseline for them: the same partition, but
+// it recurses all the way down to 2 elements and compare-swaps those by hand.
+struct OldQuickSort;
+impl Sorter for OldQuickSort {
+    fn sort_by<T, F>(&self, slice: &mut [T], mut compare: F)
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        old_quicksort(slice, &mut compare);
+    }
+}
+
+fn old_quicksort<T, F>(slice: &mut [T], compare: &mut F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    match slice.len() {
+        0 | 1 => return,
+        2 => {
+            if compare(&slice[0], &slice[1]).is_gt() {
+                slice.swap(0, 1);
+            }
+            return;
+        }
+        _ => {}
+    }
+    let (pivot, rest) = slice.split_first_mut().unwrap();
+    let (mut left, mut right) = (0, rest.len() - 1);
+    while left <= right {
+        if compare(&rest[left], pivot).is_le() {
+            left += 1;
+        } else if compare(&rest[right], pivot).is_gt() {
+            if right == 0 {
+                break;
+            }
+            right -= 1;
+        } else {
+            rest.swap(left, right);
+            left += 1;
+            if right == 0 {
+                break;
+            }
+            right -= 1;
+        }
+    }
+    slice.swap(0, left);
+    let (left, right) = slice.split_at_mut(left);
+    old_quicksort(left, compare);
+    old_quicksort(&mut right[1..], compare);
+}
+
+// the sorters have generic methods, so no dyn Sorter; instead each one gets a plain fn for each
+// element type, both from the same body
+struct Algorithm {
+    name: &'static str,
+    timed: fn(&mut [u64]),
+    counted: fn(&mut [SortEvaluator]),
+    max_len: usize, // skipped for anything longer
+}
+
+macro_rules! algorithm {
+    ($name:expr, $sorter:expr) => {
+        algorithm!($name, $sorter, usize::MAX)
+    };
+    ($name:expr, $sorter:expr, $max_len:expr) => {
+        Algorithm {
+            name: $name,
+            timed: |s| $sorter.sort(s),
+            counted: |s| $sorter.sort(s),
+            max_len: $max_len,
+        }
+    };
+}
+
+// what Default uses, but available_parallelism reads files on linux and that shouldn't be timed
+fn threads() -> usize {
+    static THREADS: OnceLock<usize> = OnceLock::new();
+    *THREADS.get_or_init(|| thread::available_parallelism().map_or(1, |n| n.get()))
+}
+
+fn algorithms() -> Vec<Algorithm> {
+    vec![
+        algorithm!("bubble", BubbleSort),
+        algorithm!("insertion-smart", InsertionSort { smart: true }),
+        algorithm!("insertion-dumb", InsertionSort { smart: false }),
+        algorithm!("selection", SelectionSort),
+        algorithm!("quick", QuickSort),
+        algorithm!("quick-no-networks", OldQuickSort),
+        algorithm!("merge-top-down", MergeSort { bottom_up: false }),
+        algorithm!("merge-bottom-up", MergeSort { bottom_up: true }),
+        algorithm!("tim", TimSort),
+        algorithm!("heap", HeapSort),
+        algorithm!("intro", IntroSort),
+        algorithm!(
+            "parallel-quick",
+            ParallelQuickSort {
+                threads: threads(),
+                cutoff: 4096
+            }
+        ),
+        algorithm!(
+            "parallel-merge",
+            ParallelMergeSort {
+                threads: threads(),
+                cutoff: 4096
+            }
+        ),
+        algorithm!("radix-lsd", RadixSort { msd: false }),
+        algorithm!("radix-msd", RadixSort { msd: true }),
+        algorithm!("counting", CountingSort),
+        // only up to 16, try --sizes 4,8,12,16 --algos network,insertion-smart,quick-no-networks
+        // (quick itself hands those sizes straight to the networks); quick against
+        // quick-no-networks shows what that buys at bigger sizes
+        algorithm!("network", NetworkSort, 16),
+        algorithm!("std", StdSorter),
+    ]
+}
+
+// all of them stay below n, so counting sort never needs a big range
+const DISTRIBUTIONS: &[&str] = &[
+    "random",
+    "sorted",
+    "reverse",
+    "nearly-sorted",
+    "duplicates",
+    "organ-pipe",
+    "all-equal",
+];
+
+fn generate(dist: &str, n: usize, rand: &mut impl Rng) -> Vec<u64> {
+    let n64 = n as u64;
+    match dist {
+        "random" => {
+            let mut values: Vec<u64> = (0..n64).collect();
+            values.shuffle(rand);
+            values
+        }
+        "sorted" => (0..n64).collect(),
+        "reverse" => (0..n64).rev().collect(),
+        "nearly-sorted" => {
+            // sorted, then one in twenty elements swapped somewhere else
+            let mut values: Vec<u64> = (0..n64).collect();
+            for _ in 0..n / 20 {
+                let (a, b) = (rand.gen_range(0..n), rand.gen_range(0..n));
+                values.swap(a, b);
+            }
+            values
+        }
+        "duplicates" => {
+            // about sqrt(n) distinct values, each one many times
+            let distinct = ((n as f64).sqrt() as u64).max(1);
+            (0..n).map(|_| rand.gen_range(0..distinct)).collect()
+        }
+        // up then down: 0 1 2 3 3 2 1 0
+        "organ-pipe" => (0..n64)
+            .map(|i| if i < n64 / 2 { i } else { n64 - 1 - i })
+            .collect(),
+        "all-equal" => vec![0; n],
+        _ => unreachable!("checked when parsing args"),
     }
 }
 
+enum Format {
+    Csv,
+    Json,
+}
+
+struct Args {
+    sizes: Vec<usize>,
+    algorithms: Vec<Algorithm>,
+    distributions: Vec<&'static str>,
+    iters: usize,
+    format: Format,
+}
+
+fn usage(err: &str) -> ! {
+    let algorithms: Vec<_> = algorithms().iter().map(|a| a.name).collect();
+    eprintln!("{}", err);
+    eprintln!(
+        "usage: bench [--sizes N,..] [--algos NAME,..] [--dists NAME,..] [--iters N] [--format csv|json]"
+    );
+    eprintln!("  algos: {}", algorithms.join(","));
+    eprintln!("  dists: {}", DISTRIBUTIONS.join(","));
+    process::exit(2);
+}
+
+fn parse_args() -> Args {
+    let mut args = Args {
+        sizes: vec![0, 1, 10, 100, 1000, 10000],
+        algorithms: algorithms(),
+        distributions: DISTRIBUTIONS.to_vec(),
+        iters: 10,
+        format: Format::Csv,
+    };
+    let mut argv = std::env::args().skip(1);
+    while let Some(flag) = argv.next() {
+        if flag == "--help" || flag == "-h" {
+            usage("");
+        }
+        let value = argv
+            .next()
+            .unwrap_or_else(|| usage(&format!("{} needs a value", flag)));
+        match &*flag {
+            "--sizes" => {
+                args.sizes = value
+                    .split(',')
+                    .map(|n| {
+                        n.parse()
+                            .unwrap_or_else(|_| usage(&format!("bad size {:?}", n)))
+                    })
+                    .collect();
+            }
+            "--algos" => {
+                let mut all = algorithms();
+                args.algorithms = value
+                    .split(',')
+                    .map(|name| match all.iter().position(|a| a.name == name) {
+                        Some(i) => all.swap_remove(i),
+                        None => usage(&format!("no algorithm called {:?}", name)),
+                    })
+                    .collect();
+            }
+            "--dists" => {
+                args.distributions = value
+                    .split(',')
+                    .map(|name| match DISTRIBUTIONS.iter().find(|&&d| d == name) {
+                        Some(d) => *d,
+                        None => usage(&format!("no distribution called {:?}", name)),
+                    })
+                    .collect();
+            }
+            "--iters" => {
+                args.iters = value
+                    .parse()
+                    .unwrap_or_else(|_| usage(&format!("bad iteration count {:?}", value)));
+            }
+            "--format" => {
+                args.format = match &*value {
+                    "csv" => Format::Csv,
+                    "json" => Format::Json,
+                    _ => usage(&format!("unknown format {:?}", value)),
+                };
+            }
+            _ => usage(&format!("unknown argument {:?}", flag)),
+        }
+    }
+    args
+}
+
+struct Measurement {
+    nanos: u128,
+    comparisons: usize,
+    moves: usize,
+}
+
 fn main() {
+    let args = parse_args();
     let mut rand = rand::thread_rng();
-    let counter = Rc::new(Cell::new(0));
-    for &n in &[0, 1, 10, 100, 1000, 10000] {
-        let mut values = Vec::with_capacity(n);
-        for _ in 0..n {
-            values.push(SortEvaluator {
-                t: rand.gen::<usize>(), cmps: Rc::clone(&counter),
-            });
-        }
-        for _ in 0..10 {
-            values.shuffle(&mut rand);
-            let took = bench(BubbleSort, &values, &counter);
-            println!("{} {} {}", "bubble", n, took);
-            let took = bench(InsertionSort { smart: true }, &values, &counter);
-            println!("{} {} {}", "insertion smart", n, took);
-            let took = bench(InsertionSort { smart: false }, &values, &counter);
-            println!("{} {} {}", "insertion dumb", n, took);
-            let took = bench(SelectionSort, &values, &counter);
-            println!("{} {} {}", "selection", n, took);
-            let took = bench(QuickSort, &values, &counter);
-            println!("{} {} {}", "quick", n, took);
-            let took = bench(StdSorter, &values, &counter);
-            println!("{} {} {}", "std", n, took);
+
+    match args.format {
+        Format::Csv => println!("algorithm,distribution,n,iteration,nanos,comparisons,moves"),
+        Format::Json => print!("["),
+    }
+    let mut first = true;
+    for &n in &args.sizes {
+        for &dist in &args.distributions {
+            for iter in 0..args.iters {
+                let values = generate(dist, n, &mut rand);
+                for algorithm in &args.algorithms {
+                    if n > algorithm.max_len {
+                        continue;
+                    }
+                    let m = bench(algorithm, &values);
+                    match args.format {
+                        Format::Csv => println!(
+                            "{},{},{},{},{},{},{}",
+                            algorithm.name, dist, n, iter, m.nanos, m.comparisons, m.moves
+                        ),
+                        Format::Json => {
+                            // names are all plain ascii, nothing to escape
+                            print!(
+                                "{}\n  {{\"algorithm\": \"{}\", \"distribution\": \"{}\", \"n\": {}, \"iteration\": {}, \"nanos\": {}, \"comparisons\": {}, \"moves\": {}}}",
+                                if first { "" } else { "," },
+                                algorithm.name, dist, n, iter, m.nanos, m.comparisons, m.moves
+                            );
+                        }
+                    }
+                    first = false;
+                }
+            }
         }
     }
+    if let Format::Json = args.format {
+        println!("\n]");
+    }
 }
 
-fn bench<T: Ord + Clone, S: Sorter>(sorter: S, values: &[T], counter: &Cell<usize>) -> usize {
-    let mut values: Vec<_> = values.into_iter().cloned().collect();
-    counter.set(0);
-    sorter.sort(&mut values);
-    let count = counter.get();
-    for i in 1..values.len() {
-        assert!(values[i] >= values[i - 1]);
+fn bench(algorithm: &Algorithm, values: &[u64]) -> Measurement {
+    let mut plain = values.to_vec();
+    let start = Instant::now();
+    (algorithm.timed)(&mut plain);
+    let nanos = start.elapsed().as_nanos();
+    assert!(
+        plain.windows(2).all(|w| w[0] <= w[1]),
+        "{} didn't sort",
+        algorithm.name
+    );
+
+    let mut counted: Vec<_> = values.iter().map(|&t| SortEvaluator::new(t)).collect();
+    // where everything starts out
+    for v in &counted {
+        v.seen();
     }
-    count
-}
\ No newline at end of file
+    CMPS.store(0, Relaxed);
+    MOVES.store(0, Relaxed);
+    (algorithm.counted)(&mut counted);
+    let comparisons = CMPS.load(Relaxed);
+    // catch the moves since each value was last compared
+    for v in &counted {
+        v.seen();
+    }
+    let moves = MOVES.load(Relaxed);
+    assert!(counted.iter().map(|v| v.t).eq(plain.iter().copied()));
+
+    Measurement {
+        nanos,
+        comparisons,
+        moves,
+    }
+}
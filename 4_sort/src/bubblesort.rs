@@ -1,17 +1,18 @@
 use super::Sorter;
+use std::cmp::Ordering;
 
 pub struct BubbleSort;
 
 impl Sorter for BubbleSort {
-    fn sort<T>(&self, slice: &mut [T])
+    fn sort_by<T, F>(&self, slice: &mut [T], mut compare: F)
     where
-        T: Ord,
+        F: FnMut(&T, &T) -> Ordering,
         {
         let mut swapped = true;
         while swapped {
             swapped = false;
             for i in 1..slice.len() { 
-                if slice[i - 1] > slice[i] {
+                if compare(&slice[i - 1], &slice[i]).is_gt() {
                     slice.swap(i - 1, i);
                     swapped = true;
                 }
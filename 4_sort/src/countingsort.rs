@@ -0,0 +1,108 @@
+use super::{KeySorter, RadixKey, RadixSort};
+use std::convert::TryFrom;
+use std::ptr;
+
+// Counts how many elements have each key, which says exactly where each key's elements start, and
+// then moves everything straight there. O(n + range) time and memory with no comparisons at all,
+// so only worth it when max - min is small (ages, scores, small enums). When it isn't (more than 4
+// buckets per element) it sorts with an LSD RadixSort instead, which is stable too.
+// Keys are read as one big-endian number, so they all need to be the same width, at most 16 bytes:
+// any of the integer types is fine.
+pub struct CountingSort;
+
+// the key as one number, its bytes read most significant first
+fn value<K: RadixKey + ?Sized>(key: &K) -> u128 {
+    (0..key.len()).fold(0, |v, i| v << 8 | key.byte(i) as u128)
+}
+
+// Stably moves every slice[i] into bucket digits[i], buckets in order, and returns where each
+// bucket starts (with len on the end, so bucket b is starts[b]..starts[b + 1]).
+pub(crate) fn counting_scatter<T>(slice: &mut [T], digits: &[usize], buckets: usize) -> Vec<usize> {
+    assert_eq!(slice.len(), digits.len());
+    let mut starts = vec![0; buckets + 1];
+    // this is also where an out of range digit panics, before anything has moved
+    for &d in digits {
+        starts[d + 1] += 1;
+    }
+    for b in 0..buckets {
+        starts[b + 1] += starts[b];
+    }
+
+    let mut next = starts.clone();
+    let mut buf: Vec<T> = Vec::with_capacity(slice.len());
+    // SAFETY: every element gets copied out to exactly one slot of buf (next[d] walks through
+    // bucket d, and the buckets cover 0..len exactly), then all of buf gets copied back over the
+    // slice. Nothing in between can panic, and buf's len stays 0 so it never drops them.
+    unsafe {
+        let src = slice.as_ptr();
+        let dst = buf.as_mut_ptr();
+        for (i, &d) in digits.iter().enumerate() {
+            ptr::copy_nonoverlapping(src.add(i), dst.add(next[d]), 1);
+            next[d] += 1;
+        }
+        ptr::copy_nonoverlapping(dst, slice.as_mut_ptr(), slice.len());
+    }
+    starts
+}
+
+impl KeySorter for CountingSort {
+    fn sort_by_key<T, K, F>(&self, slice: &mut [T], mut key: F)
+    where
+        K: RadixKey + ?Sized,
+        F: FnMut(&T) -> &K,
+    {
+        if slice.len() < 2 {
+            return;
+        }
+        let width = key(&slice[0]).len();
+        assert!(width <= 16, "CountingSort keys can be at most 16 bytes");
+        let values: Vec<u128> = slice
+            .iter()
+            .map(|x| {
+                let k = key(x);
+                assert_eq!(k.len(), width, "CountingSort keys all need the same width");
+                value(k)
+            })
+            .collect();
+
+        let min = *values.iter().min().expect("slice is non-empty");
+        let max = *values.iter().max().expect("slice is non-empty");
+        let range = match usize::try_from(max - min) {
+            Ok(r) if r / 4 < slice.len() => r + 1,
+            _ => {
+                RadixSort { msd: false }.sort_by_key(slice, key);
+                return;
+            }
+        };
+        let digits: Vec<usize> = values.iter().map(|&v| (v - min) as usize).collect();
+        counting_scatter(slice, &digits, range);
+    }
+}
+
+#[test]
+fn it_works() {
+    let mut things = vec![4, -2, 5, 3, 1, -6, 7, 8, 9];
+    CountingSort.sort(&mut things);
+    assert_eq!(things, vec![-6, -2, 1, 3, 4, 5, 7, 8, 9]);
+}
+
+#[test]
+fn wide_range() {
+    // a bucket for every u32 would be 32GB, and i64 doesn't even fit a usize range
+    let mut things = vec![u32::MAX, 0, 7];
+    CountingSort.sort(&mut things);
+    assert_eq!(things, vec![0, 7, u32::MAX]);
+    let mut things = vec![i64::MAX, i64::MIN, 0];
+    CountingSort.sort(&mut things);
+    assert_eq!(things, vec![i64::MIN, 0, i64::MAX]);
+}
+
+#[test]
+fn stable() {
+    // only the first field is the key, the second one says where each element started
+    let mut things: Vec<(u8, usize)> = vec![3, 1, 2, 1, 3, 2, 1].into_iter().zip(0..).collect();
+    let mut expected = things.clone();
+    expected.sort_by_key(|&(k, _)| k);
+    CountingSort.sort_by_key(&mut things, |(k, _)| k);
+    assert_eq!(things, expected);
+}
@@ -0,0 +1,349 @@
+use super::Sorter;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+
+// Sorting something that doesn't fit in memory: read as many records as fit in chunk_size, sort
+// them with the sorter, write them out to a temp file (a run), repeat until the input is done.
+// Then merge the runs: keep the smallest unwritten record of every run in a heap, write out
+// whichever is smallest and replace it with the next one from the same run. That's one open file
+// per run, so with more than fan_in runs the oldest ones get merged into bigger runs first, fan_in
+// at a time, until there are few enough for the last merge.
+// Records compare as raw bytes, so lines come out in byte order (which is code point order for
+// utf-8), not by locale.
+pub struct ExternalSort<S> {
+    pub sorter: S,
+    pub chunk_size: usize, // roughly how many bytes of records to hold in memory at once
+    pub temp_dir: PathBuf, // where the runs go, they're removed again when the sort is done
+    pub fan_in: usize,     // most runs merged at once, each one is an open file (at least 2)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Records {
+    // newline separated, the newline isn't part of the record: every line comes out with one,
+    // including a last line that had none in the input (like sort(1) does)
+    Lines,
+    Fixed(usize), // binary records of exactly this many bytes each
+}
+
+impl<S> ExternalSort<S> {
+    pub fn new(sorter: S) -> Self {
+        ExternalSort {
+            sorter,
+            chunk_size: 64 << 20,
+            temp_dir: env::temp_dir(),
+            fan_in: 64,
+        }
+    }
+}
+
+impl<S: Sorter> ExternalSort<S> {
+    pub fn sort_file(
+        &self,
+        input: impl AsRef<Path>,
+        output: impl AsRef<Path>,
+        records: Records,
+    ) -> io::Result<()> {
+        let input = BufReader::new(File::open(input)?);
+        let output = BufWriter::new(File::create(output)?);
+        self.sort(input, output, records)
+    }
+
+    pub fn sort<R: BufRead, W: Write>(
+        &self,
+        mut input: R,
+        mut output: W,
+        records: Records,
+    ) -> io::Result<()> {
+        assert!(self.fan_in >= 2, "can't merge fewer than 2 runs at a time");
+        let mut runs = Runs::new(&self.temp_dir);
+        let mut chunk = Vec::new();
+        loop {
+            let done = records.read_chunk(&mut input, self.chunk_size, &mut chunk)?;
+            if done && runs.paths.is_empty() {
+                // it all fit in one chunk, no need for temp files at all
+                self.sorter.sort(&mut chunk);
+                for record in &chunk {
+                    records.write(&mut output, record)?;
+                }
+                return output.flush();
+            }
+            if !chunk.is_empty() {
+                self.sorter.sort(&mut chunk);
+                runs.write(records, &chunk)?;
+                chunk.clear();
+            }
+            if done {
+                break;
+            }
+        }
+        while runs.paths.len() > self.fan_in {
+            runs.merge_oldest(records, self.fan_in)?;
+        }
+        merge(&runs.paths, records, &mut output)?;
+        output.flush()
+    }
+}
+
+impl Records {
+    // Reads records into chunk until it holds about limit bytes, returns whether the input ran out
+    fn read_chunk<R: BufRead>(
+        self,
+        input: &mut R,
+        limit: usize,
+        chunk: &mut Vec<Vec<u8>>,
+    ) -> io::Result<bool> {
+        let mut size = 0;
+        // always take at least one, or a limit smaller than a record would never get anywhere
+        while chunk.is_empty() || size < limit {
+            match self.read(input)? {
+                Some(record) => {
+                    size += record.len() + mem::size_of::<Vec<u8>>();
+                    chunk.push(record);
+                }
+                None => return Ok(true),
+            }
+        }
+        Ok(false)
+    }
+
+    fn read<R: BufRead>(self, input: &mut R) -> io::Result<Option<Vec<u8>>> {
+        match self {
+            Records::Lines => {
+                let mut line = Vec::new();
+                if input.read_until(b'\n', &mut line)? == 0 {
+                    return Ok(None);
+                }
+                if line.last() == Some(&b'\n') {
+                    line.pop();
+                }
+                Ok(Some(line))
+            }
+            Records::Fixed(width) => {
+                assert!(width > 0, "fixed width records can't be empty");
+                let mut record = vec![0; width];
+                let mut filled = 0;
+                while filled < width {
+                    match input.read(&mut record[filled..]) {
+                        Ok(0) => break,
+                        Ok(n) => filled += n,
+                        Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                        Err(e) => return Err(e),
+                    }
+                }
+                match filled {
+                    0 => Ok(None),
+                    n if n == width => Ok(Some(record)),
+                    _ => Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "input ends part way through a record",
+                    )),
+                }
+            }
+        }
+    }
+
+    fn write<W: Write>(self, output: &mut W, record: &[u8]) -> io::Result<()> {
+        output.write_all(record)?;
+        if let Records::Lines = self {
+            output.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+}
+
+// so two sorts running at once (in this process or another) don't pick the same file names
+static NEXT_SORT: AtomicUsize = AtomicUsize::new(0);
+
+// the sorted runs written so far, deleted again on drop, whether the sort worked out or not
+struct Runs<'a> {
+    dir: &'a Path,
+    id: usize,
+    paths: Vec<PathBuf>, // oldest first
+    created: usize,      // for the file names, paths shrinks again when runs get merged
+}
+
+impl<'a> Runs<'a> {
+    fn new(dir: &'a Path) -> Self {
+        Runs {
+            dir,
+            id: NEXT_SORT.fetch_add(1, Relaxed),
+            paths: Vec::new(),
+            created: 0,
+        }
+    }
+
+    // a new, empty run at the end of paths
+    fn create(&mut self) -> io::Result<BufWriter<File>> {
+        let path = self.dir.join(format!(
+            "vid5-run-{}-{}-{}",
+            process::id(),
+            self.id,
+            self.created
+        ));
+        self.created += 1;
+        // create_new: never clobber something that happens to be there already
+        let file = File::options().write(true).create_new(true).open(&path)?;
+        self.paths.push(path);
+        Ok(BufWriter::new(file))
+    }
+
+    fn write(&mut self, records: Records, chunk: &[Vec<u8>]) -> io::Result<()> {
+        let mut file = self.create()?;
+        for record in chunk {
+            records.write(&mut file, record)?;
+        }
+        file.flush()
+    }
+
+    // Merges the n oldest runs into a new one at the end. Merging oldest first means every run
+    // gets merged about as many times as every other one.
+    fn merge_oldest(&mut self, records: Records, n: usize) -> io::Result<()> {
+        let mut file = self.create()?;
+        merge(&self.paths[..n], records, &mut file)?;
+        file.flush()?;
+        for path in self.paths.drain(..n) {
+            let _ = fs::remove_file(path);
+        }
+        Ok(())
+    }
+}
+
+fn merge<W: Write>(paths: &[PathBuf], records: Records, output: &mut W) -> io::Result<()> {
+    let mut readers = Vec::with_capacity(paths.len());
+    for path in paths {
+        readers.push(BufReader::new(File::open(path)?));
+    }
+
+    // BinaryHeap pops the biggest, Reverse makes that the smallest
+    let mut heap = BinaryHeap::with_capacity(readers.len());
+    for (i, reader) in readers.iter_mut().enumerate() {
+        if let Some(record) = records.read(reader)? {
+            heap.push(Reverse((record, i)));
+        }
+    }
+    while let Some(Reverse((record, i))) = heap.pop() {
+        records.write(output, &record)?;
+        if let Some(next) = records.read(&mut readers[i])? {
+            heap.push(Reverse((next, i)));
+        }
+    }
+    Ok(())
+}
+
+impl Drop for Runs<'_> {
+    fn drop(&mut self) {
+        for path in &self.paths {
+            // nothing useful to do if it's already gone
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MergeSort, QuickSort};
+    use rand::prelude::*;
+
+    // a directory of its own, so we can check nothing gets left behind
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("vid5-test-{}-{}", name, process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn lines() {
+        let mut rand = rand::thread_rng();
+        let lines: Vec<String> = (0..5000)
+            .map(|_| format!("{:x}", rand.gen_range(0..1_000_000u32)))
+            .collect();
+        let input = lines.join("\n");
+        let mut expected = lines;
+        expected.sort();
+
+        let dir = temp_dir("lines");
+        let sort = ExternalSort {
+            chunk_size: 4096, // lots of runs
+            temp_dir: dir.clone(),
+            ..ExternalSort::new(QuickSort)
+        };
+        let mut output = Vec::new();
+        sort.sort(input.as_bytes(), &mut output, Records::Lines)
+            .unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output.lines().collect::<Vec<_>>(), expected);
+        assert!(output.ends_with('\n'));
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+        fs::remove_dir(dir).unwrap();
+    }
+
+    #[test]
+    fn fixed() {
+        let mut rand = rand::thread_rng();
+        // few enough distinct first bytes that there are plenty of ties to get through the merge
+        let records: Vec<[u8; 4]> = (0..3000)
+            .map(|_| [rand.gen_range(0..20), rand.gen(), rand.gen(), rand.gen()])
+            .collect();
+        let input: Vec<u8> = records.concat();
+        let mut expected = records;
+        expected.sort();
+
+        let dir = temp_dir("fixed");
+        let sort = ExternalSort {
+            chunk_size: 1000,
+            temp_dir: dir.clone(),
+            ..ExternalSort::new(MergeSort { bottom_up: false })
+        };
+        let mut output = Vec::new();
+        sort.sort(&input[..], &mut output, Records::Fixed(4))
+            .unwrap();
+        assert_eq!(output, expected.concat());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+        fs::remove_dir(dir).unwrap();
+    }
+
+    #[test]
+    fn more_runs_than_fan_in() {
+        let mut rand = rand::thread_rng();
+        let lines: Vec<String> = (0..2000)
+            .map(|_| rand.gen_range(0..500u32).to_string())
+            .collect();
+        // no newline at the end, it comes out with one anyway
+        let input = lines.join("\n");
+        let mut expected = lines;
+        expected.sort();
+
+        let dir = temp_dir("fan-in");
+        let sort = ExternalSort {
+            chunk_size: 100, // a few records per run, a couple hundred runs
+            temp_dir: dir.clone(),
+            fan_in: 3, // and several passes to get them down to 3
+            ..ExternalSort::new(QuickSort)
+        };
+        let mut output = Vec::new();
+        sort.sort(input.as_bytes(), &mut output, Records::Lines)
+            .unwrap();
+        let mut expected = expected.join("\n");
+        expected.push('\n');
+        assert_eq!(String::from_utf8(output).unwrap(), expected);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+        fs::remove_dir(dir).unwrap();
+    }
+
+    #[test]
+    fn partial_record() {
+        let sort = ExternalSort::new(QuickSort);
+        let err = sort
+            .sort(&[1u8, 2, 3, 4, 5][..], Vec::new(), Records::Fixed(2))
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
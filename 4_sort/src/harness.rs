@@ -0,0 +1,266 @@
+// The same checks for every sorter: every permutation of up to 8 elements (with and without
+// duplicates), a pile of random inputs, stability for the ones that promise it, and that a
+// comparison panicking part way through never loses or duplicates an element.
+//
+// The checks take the sort as a closure so the three kinds of sorter (Sorter, ParallelSorter,
+// KeySorter) can all go through them.
+
+use super::*;
+use rand::prelude::*;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+use std::sync::Arc;
+
+fn is_sorted<T: Ord>(v: &[T]) -> bool {
+    v.windows(2).all(|w| w[0] <= w[1])
+}
+
+// Heap's algorithm, calls f with every permutation of 0..n
+fn permutations(n: usize, mut f: impl FnMut(&[u32])) {
+    let mut v: Vec<u32> = (0..n as u32).collect();
+    let mut c = vec![0; n];
+    f(&v);
+    let mut i = 1;
+    while i < n {
+        if c[i] < i {
+            if i % 2 == 0 {
+                v.swap(0, i);
+            } else {
+                v.swap(c[i], i);
+            }
+            f(&v);
+            c[i] += 1;
+            i = 1;
+        } else {
+            c[i] = 0;
+            i += 1;
+        }
+    }
+}
+
+fn exhaustive(sort: impl Fn(&mut [u32])) {
+    for n in 0..=8 {
+        permutations(n, |p| {
+            let mut v = p.to_vec();
+            sort(&mut v);
+            assert!(is_sorted(&v), "{:?} sorted to {:?}", p, v);
+            // and again with every value twice: 0 0 1 1 2 2 ..
+            let mut v: Vec<u32> = p.iter().map(|x| x / 2).collect();
+            sort(&mut v);
+            assert!(is_sorted(&v), "{:?} / 2 sorted to {:?}", p, v);
+        });
+    }
+}
+
+fn random(sort: impl Fn(&mut [u32])) {
+    let mut rand = rand::thread_rng();
+    for _ in 0..200 {
+        let n = rand.gen_range(0..300);
+        // sometimes (nearly) all distinct, sometimes only a handful of values
+        let range = if rand.gen() {
+            u32::MAX
+        } else {
+            rand.gen_range(1..5)
+        };
+        let mut v: Vec<u32> = (0..n).map(|_| rand.gen_range(0..range)).collect();
+        let mut expected = v.clone();
+        expected.sort();
+        sort(&mut v);
+        assert_eq!(v, expected);
+    }
+}
+
+// only the key (.0) gets compared, .1 is where each element started
+fn stable(sort: impl Fn(&mut [(u32, usize)])) {
+    let mut rand = rand::thread_rng();
+    for _ in 0..50 {
+        let n = rand.gen_range(0..500);
+        let mut v: Vec<(u32, usize)> = (0..n).map(|i| (rand.gen_range(0..8), i)).collect();
+        let mut expected = v.clone();
+        expected.sort_by_key(|&(k, _)| k);
+        sort(&mut v);
+        assert_eq!(v, expected);
+    }
+}
+
+// panics when it's compared (or its key is read) once the fuse runs out,
+// and counts its drops so a sorter that drops a copy of something gets caught
+struct Bomb {
+    id: u32,
+    fuse: Arc<AtomicUsize>,
+    drops: Arc<AtomicUsize>,
+}
+
+impl Bomb {
+    fn tick(&self) {
+        // wraps around past 0, so only one element ever goes off
+        if self.fuse.fetch_sub(1, Relaxed) == 1 {
+            panic!("boom");
+        }
+    }
+}
+
+impl Drop for Bomb {
+    fn drop(&mut self) {
+        self.drops.fetch_add(1, Relaxed);
+    }
+}
+
+impl PartialEq for Bomb {
+    fn eq(&self, other: &Self) -> bool {
+        self.tick();
+        self.id == other.id
+    }
+}
+
+impl Eq for Bomb {}
+
+impl PartialOrd for Bomb {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Bomb {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.tick();
+        self.id.cmp(&other.id)
+    }
+}
+
+fn panic_safety(sort: impl Fn(&mut [Bomb])) {
+    let n = 200;
+    for &fuse in &[1, 2, 10, 100, 1000, usize::MAX] {
+        let mut ids: Vec<u32> = (0..n).collect();
+        ids.shuffle(&mut rand::thread_rng());
+        let fuse = Arc::new(AtomicUsize::new(fuse));
+        let drops = Arc::new(AtomicUsize::new(0));
+        let mut v: Vec<Bomb> = ids
+            .into_iter()
+            .map(|id| Bomb {
+                id,
+                fuse: Arc::clone(&fuse),
+                drops: Arc::clone(&drops),
+            })
+            .collect();
+
+        let sorted = panic::catch_unwind(AssertUnwindSafe(|| sort(&mut v))).is_ok();
+
+        // finished or not, every element has to be in there exactly once
+        let mut ids: Vec<u32> = v.iter().map(|b| b.id).collect();
+        ids.sort_unstable();
+        if ids != (0..n).collect::<Vec<_>>() || drops.load(Relaxed) != 0 {
+            // some of these are copies, dropping them would free the same Arcs twice
+            std::mem::forget(v);
+            panic!("elements were lost or duplicated");
+        }
+        if sorted {
+            fuse.store(usize::MAX, Relaxed);
+            assert!(is_sorted(&v));
+        }
+        drop(v);
+        assert_eq!(drops.load(Relaxed), n as usize);
+    }
+}
+
+fn sorter<S: Sorter>(s: S, is_stable: bool) {
+    exhaustive(|v| s.sort(v));
+    random(|v| s.sort(v));
+    if is_stable {
+        stable(|v| s.sort_by_key(v, |&(k, _)| k));
+    }
+    panic_safety(|v| s.sort(v));
+}
+
+#[test]
+fn bubble() {
+    sorter(BubbleSort, true);
+}
+
+#[test]
+fn insertion() {
+    sorter(InsertionSort { smart: true }, false);
+    sorter(InsertionSort { smart: false }, true);
+}
+
+#[test]
+fn selection() {
+    sorter(SelectionSort, false);
+}
+
+#[test]
+fn quick() {
+    sorter(QuickSort, false);
+}
+
+#[test]
+fn merge() {
+    sorter(MergeSort { bottom_up: false }, true);
+    sorter(MergeSort { bottom_up: true }, true);
+}
+
+#[test]
+fn tim() {
+    sorter(TimSort, true);
+}
+
+// only goes up to 16, smallsort checks the bigger networks
+#[test]
+fn network() {
+    exhaustive(|v| NetworkSort.sort(v));
+}
+
+#[test]
+fn heap() {
+    sorter(HeapSort, false);
+}
+
+#[test]
+fn intro() {
+    sorter(IntroSort, false);
+}
+
+// spawning threads for all 80k permutations takes a while, the random ones split plenty
+// with a cutoff this low
+#[test]
+fn parallel() {
+    let quick = ParallelQuickSort {
+        threads: 4,
+        cutoff: 8,
+    };
+    random(|v| quick.sort(v));
+    panic_safety(|v| quick.sort(v));
+
+    let merge = ParallelMergeSort {
+        threads: 4,
+        cutoff: 8,
+    };
+    random(|v| merge.sort(v));
+    stable(|v| merge.sort_by_key(v, |&(k, _)| k));
+    panic_safety(|v| merge.sort(v));
+}
+
+fn key_sorter<S: KeySorter>(s: S) {
+    exhaustive(|v| s.sort(v));
+    random(|v| s.sort(v));
+    // they're all stable, nothing ever gets compared to break a tie
+    stable(|v| s.sort_by_key(v, |(k, _)| k));
+    // no comparisons, so the key function is what blows up
+    panic_safety(|v| {
+        s.sort_by_key(v, |b| {
+            b.tick();
+            &b.id
+        })
+    });
+}
+
+#[test]
+fn radix() {
+    key_sorter(RadixSort { msd: false });
+    key_sorter(RadixSort { msd: true });
+}
+
+#[test]
+fn counting() {
+    key_sorter(CountingSort);
+}
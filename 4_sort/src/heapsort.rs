@@ -0,0 +1,59 @@
+use super::Sorter;
+use std::cmp::Ordering;
+
+pub struct HeapSort;
+
+pub(crate) fn heapsort<T, F>(slice: &mut [T], compare: &mut F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    // turn the slice into a max-heap: slice[i] >= its children slice[2i + 1] and slice[2i + 2]
+    // leaves are heaps already, so sift down every parent from the last one up to the root
+    for i in (0..slice.len() / 2).rev() {
+        sift_down(slice, i, compare);
+    }
+    // the biggest is at the root, swap it to the back and fix up the (now shorter) heap
+    // [ heap | sorted ]
+    for end in (1..slice.len()).rev() {
+        slice.swap(0, end);
+        sift_down(&mut slice[..end], 0, compare);
+    }
+}
+
+// move heap[node] down until it's bigger than both its children
+fn sift_down<T, F>(heap: &mut [T], mut node: usize, compare: &mut F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    loop {
+        let mut child = 2 * node + 1;
+        if child >= heap.len() {
+            break;
+        }
+        // swap with the bigger child, so the new parent is bigger than the other one too
+        if child + 1 < heap.len() && compare(&heap[child], &heap[child + 1]).is_lt() {
+            child += 1;
+        }
+        if compare(&heap[node], &heap[child]).is_ge() {
+            break;
+        }
+        heap.swap(node, child);
+        node = child;
+    }
+}
+
+impl Sorter for HeapSort {
+    fn sort_by<T, F>(&self, slice: &mut [T], mut compare: F)
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        heapsort(slice, &mut compare);
+    }
+}
+
+#[test]
+fn it_works() {
+    let mut things = vec![4, 2, 5, 3, 1, 6, 7, 8, 9];
+    HeapSort.sort(&mut things);
+    assert_eq!(things, vec![1, 2, 3, 4, 5, 6, 7, 8, 9]);
+}
@@ -1,13 +1,14 @@
 use super::Sorter;
+use std::cmp::Ordering;
 
 pub struct InsertionSort {
     pub smart: bool,
 }
 
 impl Sorter for InsertionSort {
-    fn sort<T>(&self, slice: &mut [T])
+    fn sort_by<T, F>(&self, slice: &mut [T], mut compare: F)
     where
-        T: Ord,
+        F: FnMut(&T, &T) -> Ordering,
     {
         for unsorted in 1..slice.len() {
             // slice[unsorted] is not sorted
@@ -18,7 +19,7 @@ impl Sorter for InsertionSort {
             // [ 1 2 3 4 | ]
             if !self.smart {
                 let mut i = unsorted;
-                while i > 0 && slice[i - 1] > slice[i] {
+                while i > 0 && compare(&slice[i - 1], &slice[i]).is_gt() {
                     slice.swap(i - 1, i);
                     i -= 1;
                 }
@@ -32,7 +33,8 @@ impl Sorter for InsertionSort {
                 //     Err(i) => i,
                 // };
                 // or
-                let (Ok(i) | Err(i)) = slice[..unsorted].binary_search(&slice[unsorted]);
+                let (Ok(i) | Err(i)) =
+                    slice[..unsorted].binary_search_by(|x| compare(x, &slice[unsorted]));
                 // or
                 // let i = slice[..unsorted].binary_search(&slice[unsorted]).unwrap_or_else(|i| i);
                 slice[i..=unsorted].rotate_right(1);
@@ -0,0 +1,144 @@
+use super::{InsertionSort, Sorter};
+use crate::heapsort::heapsort;
+use std::cmp::Ordering;
+
+// QuickSort, but it can't go quadratic: the pivot is the median of the first, middle and last
+// element (so sorted input splits down the middle), small partitions go to insertion sort, and if
+// the recursion still gets too deep the partition gets heapsorted instead.
+pub struct IntroSort;
+
+// below this, insertion sort beats partitioning
+const SMALL: usize = 16;
+
+fn introsort<T, F>(mut slice: &mut [T], mut depth: usize, compare: &mut F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    loop {
+        if slice.len() <= SMALL {
+            InsertionSort { smart: true }.sort_by(slice, &mut *compare);
+            return;
+        }
+        if depth == 0 {
+            // partitioning keeps going badly, stop trusting it
+            heapsort(slice, compare);
+            return;
+        }
+        depth -= 1;
+
+        let pivot = median_of_three(slice, compare);
+        slice.swap(0, pivot);
+        let mid = partition(slice, compare);
+        let (left, right) = slice.split_at_mut(mid);
+        // right[0] is the pivot, already in place
+        let right = &mut right[1..];
+        // recurse into the smaller side and loop on the bigger one,
+        // so the stack never gets deeper than log n even before the depth limit kicks in
+        if left.len() < right.len() {
+            introsort(left, depth, compare);
+            slice = right;
+        } else {
+            introsort(right, depth, compare);
+            slice = left;
+        }
+    }
+}
+
+// index of the median of slice[0], slice[len / 2] and slice[len - 1]
+pub(crate) fn median_of_three<T, F>(slice: &[T], compare: &mut F) -> usize
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let (a, b, c) = (0, slice.len() / 2, slice.len() - 1);
+    let mut less = |i: usize, j: usize| compare(&slice[i], &slice[j]).is_lt();
+    if less(a, b) {
+        if less(b, c) {
+            b
+        } else if less(a, c) {
+            c
+        } else {
+            a
+        }
+    } else if less(a, c) {
+        a
+    } else if less(b, c) {
+        c
+    } else {
+        b
+    }
+}
+
+// pivot is at slice[0], returns where it ends up
+// [ <= pivot | pivot | >= pivot ]
+pub(crate) fn partition<T, F>(slice: &mut [T], compare: &mut F) -> usize
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let (pivot, rest) = slice.split_first_mut().expect("slice is non-empty");
+    // rest[..left] <= pivot, rest[right..] >= pivot
+    let mut left = 0;
+    let mut right = rest.len();
+    loop {
+        // both sides stop on elements equal to the pivot, so a run of duplicates gets
+        // split down the middle rather than all ending up on one side
+        while left < right && compare(&rest[left], pivot).is_lt() {
+            left += 1;
+        }
+        while left < right && compare(&rest[right - 1], pivot).is_gt() {
+            right -= 1;
+        }
+        if left >= right {
+            break;
+        }
+        // left holds a right and right holds a left, swap them
+        right -= 1;
+        rest.swap(left, right);
+        left += 1;
+    }
+    // place the pivot at its final location, just after the last element <= it
+    slice.swap(0, left);
+    left
+}
+
+impl Sorter for IntroSort {
+    fn sort_by<T, F>(&self, slice: &mut [T], mut compare: F)
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        // twice the depth a perfectly balanced quicksort would need
+        let depth = 2 * (usize::BITS - slice.len().leading_zeros()) as usize;
+        introsort(slice, depth, &mut compare);
+    }
+}
+
+#[test]
+fn it_works() {
+    let mut things = vec![4, 2, 5, 3, 1, 6, 7, 8, 9];
+    IntroSort.sort(&mut things);
+    assert_eq!(things, vec![1, 2, 3, 4, 5, 6, 7, 8, 9]);
+}
+
+#[test]
+fn bad_inputs() {
+    // sorted, reversed and all the same are what make a first-element pivot go quadratic
+    let sorted: Vec<_> = (0..1000).collect();
+    let mut things = sorted.clone();
+    IntroSort.sort(&mut things);
+    assert_eq!(things, sorted);
+    things.reverse();
+    IntroSort.sort(&mut things);
+    assert_eq!(things, sorted);
+    let mut things = vec![7; 1000];
+    IntroSort.sort(&mut things);
+    assert_eq!(things, vec![7; 1000]);
+}
+
+#[test]
+fn heapsort_fallback() {
+    use rand::prelude::*;
+    let mut things: Vec<u32> = (0..1000).collect();
+    things.shuffle(&mut rand::thread_rng());
+    // no depth left, so the partitioning goes straight to heapsort
+    introsort(&mut things, 0, &mut u32::cmp);
+    assert_eq!(things, (0..1000).collect::<Vec<_>>());
+}
@@ -1,31 +1,119 @@
+use std::cmp::Ordering;
+
 // sorting algorithms
 // bubble sort
 pub trait Sorter {
+    // compare says how two elements order, so this can sort by a field, descending, floats (with
+    // partial_cmp + unwrap or total_cmp), anything that isn't Ord on its own
+    fn sort_by<T, F>(&self, slice: &mut [T], compare: F)
+    where
+        F: FnMut(&T, &T) -> Ordering;
+
+    fn sort<T>(&self, slice: &mut [T])
+    where
+        T: Ord,
+    {
+        self.sort_by(slice, T::cmp);
+    }
+
+    // key gets called on both sides of every comparison, so keep it cheap
+    fn sort_by_key<T, K, F>(&self, slice: &mut [T], mut key: F)
+    where
+        F: FnMut(&T) -> K,
+        K: Ord,
+    {
+        self.sort_by(slice, |a, b| key(a).cmp(&key(b)));
+    }
+}
+
+// Same thing for sorters that spread the work over several threads. It's its own trait because
+// elements get moved to other threads (T: Send) and every thread compares at once (Fn + Sync
+// rather than FnMut), which Sorter can't ask for without ruling out a lot of what it sorts.
+pub trait ParallelSorter {
+    fn sort_by<T, F>(&self, slice: &mut [T], compare: F)
+    where
+        T: Send,
+        F: Fn(&T, &T) -> Ordering + Sync;
+
+    fn sort<T>(&self, slice: &mut [T])
+    where
+        T: Ord + Send,
+    {
+        self.sort_by(slice, T::cmp);
+    }
+
+    fn sort_by_key<T, K, F>(&self, slice: &mut [T], key: F)
+    where
+        T: Send,
+        F: Fn(&T) -> K + Sync,
+        K: Ord,
+    {
+        self.sort_by(slice, |a, b| key(a).cmp(&key(b)));
+    }
+}
+
+// Sorters that never compare two elements, they bucket them by the bytes of a key instead.
+// Not a Sorter because Ord says nothing about what a value looks like inside.
+pub trait KeySorter {
+    // key borrows from the element, so it's cheap to call more than once per element
+    fn sort_by_key<T, K, F>(&self, slice: &mut [T], key: F)
+    where
+        K: RadixKey + ?Sized,
+        F: FnMut(&T) -> &K;
+
     fn sort<T>(&self, slice: &mut [T])
     where
-        T: Ord;
+        T: RadixKey,
+    {
+        self.sort_by_key(slice, |x| x);
+    }
 }
 
 mod bubblesort;
 mod insertionsort;
 mod selectionsort;
 mod quicksort;
+mod mergesort;
+mod timsort;
+mod heapsort;
+mod introsort;
+mod parallelquicksort;
+mod parallelmergesort;
+mod radixsort;
+mod countingsort;
+mod smallsort;
+mod external;
+mod select;
 
 pub use bubblesort::BubbleSort;
 pub use insertionsort::InsertionSort;
 pub use selectionsort::SelectionSort;
 pub use quicksort::QuickSort;
+pub use mergesort::MergeSort;
+pub use timsort::TimSort;
+pub use heapsort::HeapSort;
+pub use introsort::IntroSort;
+pub use parallelquicksort::ParallelQuickSort;
+pub use parallelmergesort::ParallelMergeSort;
+pub use radixsort::{RadixKey, RadixSort};
+pub use countingsort::CountingSort;
+pub use smallsort::NetworkSort;
+pub use external::{ExternalSort, Records};
+pub use select::{partial_sort, partial_sort_by, select_nth, select_nth_by, top_k, top_k_by};
+
+#[cfg(test)]
+mod harness;
 
 #[cfg(test)]
 mod tests {
     use super::*;
     struct StdSorter;
     impl Sorter for StdSorter {
-        fn sort<T>(&self, slice: &mut [T])
+        fn sort_by<T, F>(&self, slice: &mut [T], compare: F)
         where
-            T: Ord,
+            F: FnMut(&T, &T) -> Ordering,
         {
-            slice.sort();
+            slice.sort_by(compare);
         }
     }
 
@@ -35,4 +123,29 @@ mod tests {
         StdSorter.sort(&mut things);
         assert_eq!(things, vec![1, 2, 3, 4]);
     }
+
+    // floats aren't Ord, and descending
+    fn by_float_desc<S: Sorter>(sorter: S) {
+        let mut things: Vec<(i32, f64)> = vec![(1, 0.5), (2, -1.0), (3, 2.5), (4, 0.0)];
+        sorter.sort_by(&mut things, |a, b| b.1.total_cmp(&a.1));
+        assert_eq!(things, vec![(3, 2.5), (1, 0.5), (4, 0.0), (2, -1.0)]);
+    }
+
+    #[test]
+    fn by_and_by_key() {
+        by_float_desc(BubbleSort);
+        by_float_desc(InsertionSort { smart: true });
+        by_float_desc(InsertionSort { smart: false });
+        by_float_desc(SelectionSort);
+        by_float_desc(QuickSort);
+        by_float_desc(MergeSort { bottom_up: false });
+        by_float_desc(MergeSort { bottom_up: true });
+        by_float_desc(TimSort);
+        by_float_desc(HeapSort);
+        by_float_desc(IntroSort);
+
+        let mut things = vec!["ccc", "a", "bb", "dddd"];
+        QuickSort.sort_by_key(&mut things, |s| s.len());
+        assert_eq!(things, vec!["a", "bb", "ccc", "dddd"]);
+    }
 }
@@ -0,0 +1,197 @@
+use super::Sorter;
+use std::{cmp::Ordering, mem, ptr};
+
+pub struct MergeSort {
+    pub bottom_up: bool,
+}
+
+impl Sorter for MergeSort {
+    fn sort_by<T, F>(&self, slice: &mut [T], mut compare: F)
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        if self.bottom_up {
+            bottom_up(slice, &mut |a, b| compare(a, b).is_lt());
+        } else {
+            top_down(slice, &mut |a, b| compare(a, b).is_lt());
+        }
+    }
+}
+
+fn top_down<T, F>(slice: &mut [T], is_less: &mut F)
+where
+    F: FnMut(&T, &T) -> bool,
+{
+    // zero sized values are all indistinguishable, any order is sorted
+    if mem::size_of::<T>() == 0 || slice.len() < 2 {
+        return;
+    }
+    // merging never needs more than half the slice copied out
+    let mut buf = Vec::with_capacity(slice.len() / 2);
+    top_down_rec(slice, buf.as_mut_ptr(), is_less);
+}
+
+// buf needs room for slice.len() / 2 elements that nobody else is using
+pub(crate) fn top_down_rec<T, F>(slice: &mut [T], buf: *mut T, is_less: &mut F)
+where
+    F: FnMut(&T, &T) -> bool,
+{
+    if slice.len() < 2 {
+        return;
+    }
+    let mid = slice.len() / 2;
+    top_down_rec(&mut slice[..mid], buf, is_less);
+    top_down_rec(&mut slice[mid..], buf, is_less);
+    // SAFETY: buf has room for len / 2 elements, which is at least min(mid, len - mid)
+    unsafe { merge(slice, mid, buf, is_less) };
+}
+
+fn bottom_up<T, F>(slice: &mut [T], is_less: &mut F)
+where
+    F: FnMut(&T, &T) -> bool,
+{
+    if mem::size_of::<T>() == 0 || slice.len() < 2 {
+        return;
+    }
+    let len = slice.len();
+    let mut buf = Vec::with_capacity(len / 2);
+    // runs of width 1 are sorted, merge neighbouring pairs into runs twice as wide until
+    // one run covers everything
+    let mut width = 1;
+    while width < len {
+        let mut start = 0;
+        while start + width < len {
+            let end = (start + 2 * width).min(len);
+            // SAFETY: the two runs add up to at most len, so the shorter one fits in len / 2
+            unsafe { merge(&mut slice[start..end], width, buf.as_mut_ptr(), is_less) };
+            start = end;
+        }
+        width *= 2;
+    }
+}
+
+// Merges the sorted runs v[..mid] and v[mid..] in place, stably.
+// The shorter run gets copied out to buf, and then merged back in from the side where the gap
+// opens up. If is_less panics part way through, MergeHole copies what's still in buf back into
+// the gap, so every element ends up in the slice exactly once either way.
+//
+// SAFETY: buf has to be valid for writes of min(mid, v.len() - mid) elements and not overlap v,
+// and T must not be zero sized.
+pub(crate) unsafe fn merge<T, F>(v: &mut [T], mid: usize, buf: *mut T, is_less: &mut F)
+where
+    F: FnMut(&T, &T) -> bool,
+{
+    let len = v.len();
+    if mid == 0 || mid >= len {
+        return;
+    }
+    // already in order, the most common case for nearly sorted input and it's one comparison
+    if !is_less(&v[mid], &v[mid - 1]) {
+        return;
+    }
+
+    let v = v.as_mut_ptr();
+    let v_mid = v.add(mid);
+    let v_end = v.add(len);
+
+    if mid <= len - mid {
+        // left run is shorter: copy it out and merge forwards into the front of v
+        ptr::copy_nonoverlapping(v, buf, mid);
+        let mut hole = MergeHole {
+            start: buf,
+            end: buf.add(mid),
+            dest: v,
+        };
+        let left = &mut hole.start;
+        let mut right = v_mid;
+        let out = &mut hole.dest;
+        while *left < hole.end && right < v_end {
+            // only take from the right if it's strictly smaller, equal elements keep their order
+            let from = if is_less(&*right, &**left) {
+                get_and_increment(&mut right)
+            } else {
+                get_and_increment(left)
+            };
+            ptr::copy_nonoverlapping(from, get_and_increment(out), 1);
+        }
+        // whatever is left of the left run is exactly the size of the gap before `right`
+    } else {
+        // right run is shorter: copy it out and merge backwards into the back of v
+        ptr::copy_nonoverlapping(v_mid, buf, len - mid);
+        let mut hole = MergeHole {
+            start: buf,
+            end: buf.add(len - mid),
+            dest: v_mid,
+        };
+        let left = &mut hole.dest;
+        let right = &mut hole.end;
+        let mut out = v_end;
+        while v < *left && buf < *right {
+            // only take from the left if it's strictly bigger, equal elements keep their order
+            let from = if is_less(&*right.sub(1), &*left.sub(1)) {
+                decrement_and_get(left)
+            } else {
+                decrement_and_get(right)
+            };
+            ptr::copy_nonoverlapping(from, decrement_and_get(&mut out), 1);
+        }
+        // whatever is left of the right run is exactly the size of the gap after `left`
+    }
+    // hole goes out of scope here and fills the gap
+}
+
+pub(crate) unsafe fn get_and_increment<T>(ptr: &mut *mut T) -> *mut T {
+    let old = *ptr;
+    *ptr = ptr.add(1);
+    old
+}
+
+pub(crate) unsafe fn decrement_and_get<T>(ptr: &mut *mut T) -> *mut T {
+    *ptr = ptr.sub(1);
+    *ptr
+}
+
+// buf[start..end] still has to go back into the slice at dest
+pub(crate) struct MergeHole<T> {
+    pub(crate) start: *mut T,
+    pub(crate) end: *mut T,
+    pub(crate) dest: *mut T,
+}
+
+impl<T> Drop for MergeHole<T> {
+    fn drop(&mut self) {
+        // SAFETY: T isn't zero sized (checked by the callers), start..end is what's left in buf,
+        // and the merge leaves a gap of exactly that size at dest
+        unsafe {
+            let len = self.end.offset_from(self.start) as usize;
+            ptr::copy_nonoverlapping(self.start, self.dest, len);
+        }
+    }
+}
+
+#[test]
+fn it_works_top_down() {
+    let mut things = vec![4, 2, 5, 3, 1, 6, 7, 8, 9];
+    MergeSort { bottom_up: false }.sort(&mut things);
+    assert_eq!(things, vec![1, 2, 3, 4, 5, 6, 7, 8, 9]);
+}
+
+#[test]
+fn it_works_bottom_up() {
+    let mut things = vec![4, 2, 5, 3, 1, 6, 7, 8, 9];
+    MergeSort { bottom_up: true }.sort(&mut things);
+    assert_eq!(things, vec![1, 2, 3, 4, 5, 6, 7, 8, 9]);
+}
+
+#[test]
+fn stable() {
+    // only the first field is compared, the second one says where each element started
+    let mut things: Vec<(i32, usize)> = vec![3, 1, 2, 1, 3, 2, 1].into_iter().zip(0..).collect();
+    let mut expected = things.clone();
+    expected.sort_by_key(|&(k, _)| k);
+    let mut top = things.clone();
+    top_down(&mut top, &mut |a, b| a.0 < b.0);
+    assert_eq!(top, expected);
+    bottom_up(&mut things, &mut |a, b| a.0 < b.0);
+    assert_eq!(things, expected);
+}
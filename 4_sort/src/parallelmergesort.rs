@@ -0,0 +1,102 @@
+use super::ParallelSorter;
+use crate::mergesort::{merge, top_down_rec};
+use std::cmp::Ordering;
+use std::mem::{self, MaybeUninit};
+use std::thread;
+
+// Top-down merge sort with the two halves sorted on different threads, then merged on this one.
+// Every half gets its own part of the merge buffer (a half of length n only ever needs n / 2),
+// so the threads never share anything but the comparison.
+pub struct ParallelMergeSort {
+    pub threads: usize,
+    pub cutoff: usize, // runs this short (or shorter) get sorted on the current thread
+}
+
+impl Default for ParallelMergeSort {
+    fn default() -> Self {
+        ParallelMergeSort {
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            cutoff: 4096,
+        }
+    }
+}
+
+// buf has room for slice.len() / 2 elements
+fn par_mergesort<T, F>(
+    slice: &mut [T],
+    buf: &mut [MaybeUninit<T>],
+    threads: usize,
+    cutoff: usize,
+    compare: &F,
+) where
+    T: Send,
+    F: Fn(&T, &T) -> Ordering + Sync,
+{
+    let mut is_less = |a: &T, b: &T| compare(a, b).is_lt();
+    if threads <= 1 || slice.len() <= cutoff.max(2) {
+        top_down_rec(slice, buf.as_mut_ptr().cast(), &mut is_less);
+        return;
+    }
+
+    let mid = slice.len() / 2;
+    {
+        let (left, right) = slice.split_at_mut(mid);
+        let (left_buf, right_buf) = buf.split_at_mut(mid / 2);
+        thread::scope(|s| {
+            s.spawn(|| par_mergesort(left, left_buf, threads / 2, cutoff, compare));
+            par_mergesort(right, right_buf, threads - threads / 2, cutoff, compare);
+        });
+    }
+    // SAFETY: buf has room for len / 2 elements, which is at least min(mid, len - mid),
+    // and the halves are done with it
+    unsafe { merge(slice, mid, buf.as_mut_ptr().cast(), &mut is_less) };
+}
+
+impl ParallelSorter for ParallelMergeSort {
+    fn sort_by<T, F>(&self, slice: &mut [T], compare: F)
+    where
+        T: Send,
+        F: Fn(&T, &T) -> Ordering + Sync,
+    {
+        // zero sized values are all indistinguishable, any order is sorted
+        if mem::size_of::<T>() == 0 || slice.len() < 2 {
+            return;
+        }
+        let mut buf = Vec::with_capacity(slice.len() / 2);
+        par_mergesort(
+            slice,
+            buf.spare_capacity_mut(),
+            self.threads,
+            self.cutoff,
+            &compare,
+        );
+    }
+}
+
+#[test]
+fn it_works() {
+    let mut things = vec![4, 2, 5, 3, 1, 6, 7, 8, 9];
+    ParallelMergeSort {
+        threads: 4,
+        cutoff: 2,
+    }
+    .sort(&mut things);
+    assert_eq!(things, vec![1, 2, 3, 4, 5, 6, 7, 8, 9]);
+}
+
+#[test]
+fn stable() {
+    use rand::prelude::*;
+    let mut rand = rand::thread_rng();
+    // only the key is compared, the index says where each element started
+    let mut things: Vec<(u16, usize)> =
+        (0..100_000).map(|i| (rand.gen_range(0..1000), i)).collect();
+    let mut expected = things.clone();
+    expected.sort_by_key(|&(k, _)| k);
+    ParallelMergeSort {
+        threads: 8,
+        cutoff: 100,
+    }
+    .sort_by_key(&mut things, |&(k, _)| k);
+    assert_eq!(things, expected);
+}
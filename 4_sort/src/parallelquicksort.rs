@@ -0,0 +1,82 @@
+use super::{IntroSort, ParallelSorter, Sorter};
+use crate::introsort::{median_of_three, partition};
+use std::cmp::Ordering;
+use std::thread;
+
+// Partition, then sort the two sides on different threads. They're disjoint halves of the slice
+// (split_at_mut), so scoped threads can borrow them without any locking. Each split hands half
+// of the remaining threads to each side; once out of threads, or once a side is small enough that
+// starting a thread costs more than it saves, the rest is plain IntroSort.
+pub struct ParallelQuickSort {
+    pub threads: usize,
+    pub cutoff: usize, // partitions this small (or smaller) get sorted on the current thread
+}
+
+impl Default for ParallelQuickSort {
+    fn default() -> Self {
+        ParallelQuickSort {
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            cutoff: 4096,
+        }
+    }
+}
+
+fn par_quicksort<T, F>(slice: &mut [T], threads: usize, cutoff: usize, compare: &F)
+where
+    T: Send,
+    F: Fn(&T, &T) -> Ordering + Sync,
+{
+    if threads <= 1 || slice.len() <= cutoff.max(2) {
+        IntroSort.sort_by(slice, compare);
+        return;
+    }
+
+    let pivot = median_of_three(slice, &mut &*compare);
+    slice.swap(0, pivot);
+    let mid = partition(slice, &mut &*compare);
+    let (left, right) = slice.split_at_mut(mid);
+    // right[0] is the pivot, already in place
+    let right = &mut right[1..];
+
+    thread::scope(|s| {
+        s.spawn(|| par_quicksort(left, threads / 2, cutoff, compare));
+        // this thread does the other side itself rather than sitting there waiting
+        par_quicksort(right, threads - threads / 2, cutoff, compare);
+    });
+}
+
+impl ParallelSorter for ParallelQuickSort {
+    fn sort_by<T, F>(&self, slice: &mut [T], compare: F)
+    where
+        T: Send,
+        F: Fn(&T, &T) -> Ordering + Sync,
+    {
+        par_quicksort(slice, self.threads, self.cutoff, &compare);
+    }
+}
+
+#[test]
+fn it_works() {
+    let mut things = vec![4, 2, 5, 3, 1, 6, 7, 8, 9];
+    ParallelQuickSort {
+        threads: 4,
+        cutoff: 2,
+    }
+    .sort(&mut things);
+    assert_eq!(things, vec![1, 2, 3, 4, 5, 6, 7, 8, 9]);
+}
+
+#[test]
+fn many() {
+    use rand::prelude::*;
+    let mut rand = rand::thread_rng();
+    let mut things: Vec<u32> = (0..100_000).map(|_| rand.gen_range(0..1000)).collect();
+    let mut expected = things.clone();
+    expected.sort_by(|a, b| b.cmp(a));
+    ParallelQuickSort {
+        threads: 8,
+        cutoff: 100,
+    }
+    .sort_by(&mut things, |a, b| b.cmp(a));
+    assert_eq!(things, expected);
+}
@@ -1,29 +1,47 @@
 use super::Sorter;
+use crate::smallsort::{network_sort, MAX_NETWORK};
+use std::cmp::Ordering;
 
 pub struct QuickSort;
 
-fn quicksort<T: Ord>(slice: &mut [T]) {
-    match slice.len() {
-        0 | 1 => return,
-        2 => {
-            if slice[0] > slice[1] {
-                slice.swap(0, 1);
-            }
-            return;
-        }
-        _ => {}
+fn quicksort<T, F>(slice: &mut [T], compare: &mut F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    // small enough for a sorting network (used to be just the 2 case, compare-swap by hand)
+    // no recursion, and no branches on the comparisons
+    if slice.len() <= MAX_NETWORK {
+        network_sort(slice, &mut |a, b| compare(a, b).is_lt());
+        return;
     }
 
+    let mid = partition(slice, compare);
+    let (left, right) = slice.split_at_mut(mid); // split at the pivot
+    if let (Some(l), Some(r)) = (left.last(), right.first()) {
+        assert!(compare(l, r).is_le());
+    }
+    quicksort(left, compare);
+    quicksort(&mut right[1..], compare);
+}
 
+// pivot is slice[0], moves everything <= it to the left of it and everything > it to the right,
+// and returns where the pivot ends up
+fn partition<T, F>(slice: &mut [T], compare: &mut F) -> usize
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
     let (pivot, rest) = slice.split_first_mut().expect("!slice is non-empty");
+    if rest.is_empty() {
+        return 0;
+    }
     let mut left = 0;
     let mut right = rest.len() - 1;
     // consider boundary conditions (what happens are start + finish)
     while left <= right {
-        if &rest[left] <= pivot {
+        if compare(&rest[left], pivot).is_le() {
             // already on correct side
             left += 1;
-        } else if &rest[right] > pivot {
+        } else if compare(&rest[right], pivot).is_gt() {
             // right already on correct side
             // avoid unnecessary swaps back and forth)
             if right == 0 {
@@ -47,19 +65,15 @@ fn quicksort<T: Ord>(slice: &mut [T]) {
     
     // place the pivot at its final location
     slice.swap(0, left - 1);
-    
-    let (left, right) = slice.split_at_mut(left - 1); // split at the pivot
-    assert!(left.last() <= right.first());
-    quicksort(left);
-    quicksort(&mut right[1..]);
+    left - 1
 }
 
 impl Sorter for QuickSort {
-    fn sort<T>(&self, slice: &mut [T])
+    fn sort_by<T, F>(&self, slice: &mut [T], mut compare: F)
     where
-        T: Ord,
+        F: FnMut(&T, &T) -> Ordering,
     {
-        quicksort(slice);
+        quicksort(slice, &mut compare);
     }
 }
 
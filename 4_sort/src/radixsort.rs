@@ -0,0 +1,259 @@
+use super::{InsertionSort, KeySorter, Sorter};
+use crate::countingsort::counting_scatter;
+use std::cmp::Ordering;
+use std::mem;
+
+// Sorts by the key one byte at a time, bucketing on each byte with a counting sort, so it never
+// compares two elements. Either from the last byte to the first (LSD: every pass is stable, so
+// later passes keep the order the earlier ones set up among equal bytes), or from the first byte
+// down (MSD: bucket on the first byte, then sort each bucket on the next one, and so on).
+// Keys can be different lengths, a key that ends sorts before any that keep going.
+pub struct RadixSort {
+    pub msd: bool,
+}
+
+// What radix sort needs to know about a key: its bytes, most significant first, in an order where
+// comparing them one by one gives the same answer as comparing the keys
+pub trait RadixKey {
+    fn len(&self) -> usize;
+    fn byte(&self, i: usize) -> u8;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+macro_rules! unsigned_key {
+    ($($t:ty)*) => {$(
+        impl RadixKey for $t {
+            fn len(&self) -> usize {
+                mem::size_of::<$t>()
+            }
+
+            fn byte(&self, i: usize) -> u8 {
+                self.to_be_bytes()[i]
+            }
+        }
+    )*};
+}
+
+unsigned_key!(u8 u16 u32 u64 u128 usize);
+
+macro_rules! signed_key {
+    ($($t:ty => $u:ty)*) => {$(
+        impl RadixKey for $t {
+            fn len(&self) -> usize {
+                mem::size_of::<$t>()
+            }
+
+            fn byte(&self, i: usize) -> u8 {
+                // flipping the sign bit puts the negatives (which have it set) before the rest,
+                // and two's complement already orders each half the right way
+                ((*self as $u) ^ (1 << (<$u>::BITS - 1))).to_be_bytes()[i]
+            }
+        }
+    )*};
+}
+
+signed_key!(i8 => u8 i16 => u16 i32 => u32 i64 => u64 i128 => u128 isize => usize);
+
+impl RadixKey for [u8] {
+    fn len(&self) -> usize {
+        <[u8]>::len(self)
+    }
+
+    fn byte(&self, i: usize) -> u8 {
+        self[i]
+    }
+}
+
+impl RadixKey for Vec<u8> {
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+
+    fn byte(&self, i: usize) -> u8 {
+        self[i]
+    }
+}
+
+// utf-8 sorts the same bytewise as by code point, which is what Ord for str does
+impl RadixKey for str {
+    fn len(&self) -> usize {
+        str::len(self)
+    }
+
+    fn byte(&self, i: usize) -> u8 {
+        self.as_bytes()[i]
+    }
+}
+
+impl RadixKey for String {
+    fn len(&self) -> usize {
+        String::len(self)
+    }
+
+    fn byte(&self, i: usize) -> u8 {
+        self.as_bytes()[i]
+    }
+}
+
+// 0 is "the key already ended", byte b goes in bucket b + 1, so shorter keys come first
+const BUCKETS: usize = 257;
+
+// MSD buckets smaller than this get insertion sorted, counting 257 buckets for a handful of
+// elements costs far more than comparing them
+const SMALL: usize = 32;
+
+fn digit<K: RadixKey + ?Sized>(key: &K, i: usize) -> usize {
+    if i < key.len() {
+        key.byte(i) as usize + 1
+    } else {
+        0
+    }
+}
+
+fn lsd<T, K, F>(slice: &mut [T], key: &mut F)
+where
+    K: RadixKey + ?Sized,
+    F: FnMut(&T) -> &K,
+{
+    let width = slice.iter().map(|x| key(x).len()).max().unwrap_or(0);
+    for i in (0..width).rev() {
+        let digits: Vec<usize> = slice.iter().map(|x| digit(key(x), i)).collect();
+        // every key has the same byte here, nothing would move
+        // (small numbers in a big type are mostly leading zeroes)
+        if digits.iter().all(|&d| d == digits[0]) {
+            continue;
+        }
+        counting_scatter(slice, &digits, BUCKETS);
+    }
+}
+
+// Compares two keys that are known to agree on everything before depth.
+fn compare_from<K: RadixKey + ?Sized>(a: &K, b: &K, depth: usize) -> Ordering {
+    for i in depth..a.len().min(b.len()) {
+        match a.byte(i).cmp(&b.byte(i)) {
+            Ordering::Equal => {}
+            unequal => return unequal,
+        }
+    }
+    a.len().cmp(&b.len())
+}
+
+fn msd<T, K, F>(slice: &mut [T], key: &mut F)
+where
+    K: RadixKey + ?Sized,
+    F: FnMut(&T) -> &K,
+{
+    // Buckets still to sort, as (range, depth). A stack rather than recursion: it goes one level
+    // deeper per key byte, and keys can be as long as they like.
+    let mut todo = vec![(0..slice.len(), 0)];
+    while let Some((range, mut depth)) = todo.pop() {
+        let bucket = &mut slice[range.clone()];
+        if bucket.len() < 2 {
+            continue;
+        }
+        if bucket.len() < SMALL {
+            // stable, like the scatter
+            InsertionSort { smart: false }
+                .sort_by(bucket, |a, b| compare_from(key(a), key(b), depth));
+            continue;
+        }
+        let digits = loop {
+            let digits: Vec<usize> = bucket.iter().map(|x| digit(key(x), depth)).collect();
+            // every key has the same byte here, nothing would move: on to the next one, unless
+            // they've all ended, then they're all equal
+            if digits.iter().all(|&d| d == digits[0]) {
+                if digits[0] == 0 {
+                    break None;
+                }
+                depth += 1;
+                continue;
+            }
+            break Some(digits);
+        };
+        if let Some(digits) = digits {
+            let starts = counting_scatter(bucket, &digits, BUCKETS);
+            // bucket 0 is the keys that ended at this depth, they're all equal
+            for b in 1..BUCKETS {
+                let start = range.start;
+                todo.push((start + starts[b]..start + starts[b + 1], depth + 1));
+            }
+        }
+    }
+}
+
+impl KeySorter for RadixSort {
+    fn sort_by_key<T, K, F>(&self, slice: &mut [T], mut key: F)
+    where
+        K: RadixKey + ?Sized,
+        F: FnMut(&T) -> &K,
+    {
+        if self.msd {
+            msd(slice, &mut key);
+        } else {
+            lsd(slice, &mut key);
+        }
+    }
+}
+
+#[test]
+fn it_works() {
+    for msd in [false, true] {
+        let mut things = vec![4, 2, 5, 3, 1, 6, 7, 8, 9];
+        RadixSort { msd }.sort(&mut things);
+        assert_eq!(things, vec![1, 2, 3, 4, 5, 6, 7, 8, 9]);
+    }
+}
+
+#[test]
+fn signed_and_strings() {
+    for msd in [false, true] {
+        let mut things = vec![300i64, -1, i64::MIN, 0, i64::MAX, -300];
+        RadixSort { msd }.sort(&mut things);
+        assert_eq!(things, vec![i64::MIN, -300, -1, 0, 300, i64::MAX]);
+
+        let mut things = vec!["bb", "a", "", "ab", "b", "abc"];
+        RadixSort { msd }.sort_by_key(&mut things, |s| *s);
+        assert_eq!(things, vec!["", "a", "ab", "abc", "b", "bb"]);
+    }
+}
+
+#[test]
+fn long_equal_keys() {
+    // a byte deeper for every level, far more of them than there's stack for
+    let long = vec![b'a'; 100_000];
+    let mut things = vec![long.clone(), b"b".to_vec(), long.clone()];
+    RadixSort { msd: true }.sort(&mut things);
+    assert_eq!(things, vec![long.clone(), long.clone(), b"b".to_vec()]);
+
+    // and a bucket too big to be insertion sorted, with a difference right at the end
+    let mut things: Vec<Vec<u8>> = (0..100u8)
+        .rev()
+        .map(|i| {
+            let mut key = long.clone();
+            key.push(i);
+            key
+        })
+        .collect();
+    let mut expected = things.clone();
+    expected.sort();
+    RadixSort { msd: true }.sort(&mut things);
+    assert_eq!(things, expected);
+}
+
+#[test]
+fn stable() {
+    use rand::prelude::*;
+    let mut rand = rand::thread_rng();
+    // only the key is looked at, the index says where each element started
+    let things: Vec<(u32, usize)> = (0..2000).map(|i| (rand.gen_range(0..70000), i)).collect();
+    let mut expected = things.clone();
+    expected.sort_by_key(|&(k, _)| k);
+    for msd in [false, true] {
+        let mut things = things.clone();
+        RadixSort { msd }.sort_by_key(&mut things, |(k, _)| k);
+        assert_eq!(things, expected);
+    }
+}
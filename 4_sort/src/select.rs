@@ -0,0 +1,174 @@
+use super::{IntroSort, Sorter};
+use crate::heapsort::heapsort;
+use crate::introsort::{median_of_three, partition};
+use crate::smallsort::{network_sort, MAX_NETWORK};
+use std::cmp::Ordering;
+
+// When only part of the order matters. Quicksort partitions and then recurses into both sides;
+// to find the k-th element only the side k is on needs looking at, which is O(n) on average.
+// Like IntroSort it picks median-of-three pivots and gives up on partitioning past a depth limit
+// (heapsorting what's left), so a bad input can't make it quadratic. It uses IntroSort's partition
+// too, which splits elements equal to the pivot between both sides, so lots of duplicates still
+// halve the slice every time instead of taking one element off it.
+
+// Moves the element that would be at k if slice were sorted to k, everything before it <= it and
+// everything after it >= it. Panics if k is out of bounds.
+pub fn select_nth<T: Ord>(slice: &mut [T], k: usize) -> &mut T {
+    select_nth_by(slice, k, T::cmp)
+}
+
+pub fn select_nth_by<T, F>(slice: &mut [T], k: usize, mut compare: F) -> &mut T
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    assert!(
+        k < slice.len(),
+        "select_nth: k is {} but the len is {}",
+        k,
+        slice.len()
+    );
+    select(slice, k, &mut compare);
+    &mut slice[k]
+}
+
+fn select<T, F>(mut slice: &mut [T], mut k: usize, compare: &mut F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    // twice the depth it'd take if every pivot split down the middle
+    let mut depth = 2 * (usize::BITS - slice.len().leading_zeros()) as usize;
+    loop {
+        if slice.len() <= MAX_NETWORK {
+            network_sort(slice, &mut |a, b| compare(a, b).is_lt());
+            return;
+        }
+        if depth == 0 {
+            heapsort(slice, compare);
+            return;
+        }
+        depth -= 1;
+
+        let pivot = median_of_three(slice, compare);
+        slice.swap(0, pivot);
+        let mid = partition(slice, compare);
+        // only keep going on the side k is on
+        let rest = slice;
+        match k.cmp(&mid) {
+            Ordering::Equal => return,
+            Ordering::Less => slice = &mut rest[..mid],
+            Ordering::Greater => {
+                slice = &mut rest[mid + 1..];
+                k -= mid + 1;
+            }
+        }
+    }
+}
+
+// Sorts just the k smallest, into slice[..k]. The rest end up after them in no particular order.
+pub fn partial_sort<T: Ord>(slice: &mut [T], k: usize) {
+    partial_sort_by(slice, k, T::cmp)
+}
+
+pub fn partial_sort_by<T, F>(slice: &mut [T], k: usize, mut compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let k = k.min(slice.len());
+    if k == 0 {
+        return;
+    }
+    // the k - 1 before it are the smallest ones, they just need putting in order
+    select(slice, k - 1, &mut compare);
+    IntroSort.sort_by(&mut slice[..k - 1], compare);
+}
+
+// The k smallest items, sorted, without holding more than 2k of them at once.
+// Collects up to 2k, then selects the k smallest of those and drops the rest, so every item
+// costs O(1) on average.
+pub fn top_k<T: Ord>(iter: impl IntoIterator<Item = T>, k: usize) -> Vec<T> {
+    top_k_by(iter, k, T::cmp)
+}
+
+pub fn top_k_by<T, F>(iter: impl IntoIterator<Item = T>, k: usize, mut compare: F) -> Vec<T>
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let mut keep = Vec::new();
+    if k == 0 {
+        return keep;
+    }
+    for item in iter {
+        keep.push(item);
+        // saturating, a huge k just means never getting there
+        if keep.len() == k.saturating_mul(2) {
+            select(&mut keep, k - 1, &mut compare);
+            keep.truncate(k);
+        }
+    }
+    partial_sort_by(&mut keep, k, compare);
+    keep.truncate(k);
+    keep
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::prelude::*;
+
+    #[test]
+    fn it_works() {
+        let mut things = vec![4, 2, 5, 3, 1, 6, 7, 8, 9];
+        assert_eq!(*select_nth(&mut things, 4), 5);
+        partial_sort(&mut things, 3);
+        assert_eq!(things[..3], [1, 2, 3]);
+        assert_eq!(top_k(vec![4, 2, 5, 3, 1, 6, 7, 8, 9], 2), vec![1, 2]);
+    }
+
+    #[test]
+    fn random() {
+        let mut rand = rand::thread_rng();
+        for _ in 0..200 {
+            let n = rand.gen_range(1..500);
+            // sometimes only a handful of values, lots of elements equal to the pivot
+            let range = if rand.gen() { u32::MAX } else { 4 };
+            let things: Vec<u32> = (0..n).map(|_| rand.gen_range(0..range)).collect();
+            let mut sorted = things.clone();
+            sorted.sort();
+            let k = rand.gen_range(0..n);
+
+            let mut v = things.clone();
+            assert_eq!(*select_nth(&mut v, k), sorted[k]);
+            assert!(v[..k].iter().all(|x| *x <= v[k]));
+            assert!(v[k..].iter().all(|x| *x >= v[k]));
+
+            let mut v = things.clone();
+            partial_sort(&mut v, k);
+            assert_eq!(v[..k], sorted[..k]);
+
+            assert_eq!(top_k(things.iter().copied(), k), sorted[..k]);
+        }
+    }
+
+    #[test]
+    fn all_equal() {
+        // O(n), not the n log n it takes after falling back to heapsort
+        let n = 10_000;
+        let mut v = vec![7; n];
+        let mut cmps = 0;
+        select_nth_by(&mut v, n / 2, |a, b| {
+            cmps += 1;
+            a.cmp(b)
+        });
+        assert!(cmps < 10 * n, "{} comparisons", cmps);
+        assert_eq!(top_k(vec![1, 2, 3], usize::MAX), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn by() {
+        // the 3 biggest
+        let things = vec![4, 2, 5, 3, 1, 6, 7, 8, 9];
+        assert_eq!(top_k_by(things, 3, |a, b| b.cmp(a)), vec![9, 8, 7]);
+        assert_eq!(top_k(vec![1, 2], 5), vec![1, 2]);
+        assert!(top_k(vec![1, 2], 0).is_empty());
+    }
+}
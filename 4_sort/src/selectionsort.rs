@@ -1,11 +1,12 @@
 use super::Sorter;
+use std::cmp::Ordering;
 
 pub struct SelectionSort;
 
 impl Sorter for SelectionSort {
-    fn sort<T>(&self, slice: &mut [T])
+    fn sort_by<T, F>(&self, slice: &mut [T], mut compare: F)
     where
-        T: Ord,
+        F: FnMut(&T, &T) -> Ordering,
     {
         // can't assume first element is sorted, needs to be smallest element
         for unsorted in 0..slice.len() {
@@ -13,7 +14,7 @@ impl Sorter for SelectionSort {
             let smallest_in_rest = slice[unsorted..]
                 .iter()
                 .enumerate()
-                .min_by_key(|&(_, v)| v) // pull out just the value
+                .min_by(|(_, a), (_, b)| compare(a, b)) // compare just the values
                 .map(|(i, _)| unsorted + i)
                 .expect("!slice is non-empty"); // we know it'll never be empty
             // or
@@ -21,7 +22,7 @@ impl Sorter for SelectionSort {
             // let mut smallest_in_rest_2 = unsorted;
             // // check for smallest element in remainder and insert it where it goes
             // for i in (unsorted + 1)..slice.len() {
-            //     if slice[i] < slice[smallest_in_rest_2] {
+            //     if compare(&slice[i], &slice[smallest_in_rest_2]).is_lt() {
             //         smallest_in_rest_2 = i;
             //     }
             // }
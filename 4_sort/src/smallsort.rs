@@ -0,0 +1,134 @@
+use super::Sorter;
+use std::cmp::Ordering;
+use std::mem::ManuallyDrop;
+use std::ptr;
+
+// Sorts up to 16 elements with a sorting network: a fixed list of compare-swaps that sorts any
+// input of that size, so which pairs get compared never depends on the data. Nothing to
+// mispredict, and each compare-swap is done without a branch too (see swap_if_less).
+// Panics on anything longer than MAX_NETWORK.
+pub struct NetworkSort;
+
+pub(crate) const MAX_NETWORK: usize = 16;
+
+// NETWORKS[n] sorts n elements. The fewest comparators known for each size, proven optimal up to
+// 12; 14 and 15 are 16's network with the top wires cut off (an infinity on a wire never moves,
+// so the comparators on it can go). (a, b) puts the smaller of v[a] and v[b] at a, always a < b.
+#[rustfmt::skip]
+const NETWORKS: [&[(u8, u8)]; MAX_NETWORK + 1] = [
+    // 0 elements, 0 comparators
+    &[],
+    // 1 element, 0 comparators
+    &[],
+    // 2 elements, 1 comparator
+    &[(0, 1)],
+    // 3 elements, 3 comparators
+    &[(0, 2), (0, 1), (1, 2)],
+    // 4 elements, 5 comparators
+    &[(0, 1), (2, 3), (0, 2), (1, 3), (1, 2)],
+    // 5 elements, 9 comparators
+    &[(0, 1), (3, 4), (2, 4), (2, 3), (1, 4), (0, 3), (0, 2), (1, 3), (1, 2)],
+    // 6 elements, 12 comparators
+    &[(1, 2), (4, 5), (0, 2), (3, 5), (0, 1), (3, 4), (2, 5), (0, 3), (1, 4), (2, 4), (1, 3), (2, 3)],
+    // 7 elements, 16 comparators
+    &[(1, 2), (3, 4), (5, 6), (0, 2), (3, 5), (4, 6), (0, 1), (4, 5), (2, 6), (0, 4), (1, 5), (0, 3), (2, 5), (1, 3), (2, 4), (2, 3)],
+    // 8 elements, 19 comparators
+    &[(0, 2), (1, 3), (4, 6), (5, 7), (0, 4), (1, 5), (2, 6), (3, 7), (0, 1), (2, 3), (4, 5), (6, 7), (2, 4), (3, 5), (1, 4), (3, 6), (1, 2), (3, 4), (5, 6)],
+    // 9 elements, 25 comparators
+    &[(0, 1), (3, 4), (6, 7), (1, 2), (4, 5), (7, 8), (0, 1), (3, 4), (6, 7), (0, 3), (3, 6), (0, 3), (1, 4), (4, 7), (1, 4), (2, 5), (5, 8), (2, 5), (1, 3), (5, 7), (2, 6), (4, 6), (2, 4), (2, 3), (5, 6)],
+    // 10 elements, 29 comparators
+    &[(4, 9), (3, 8), (2, 7), (1, 6), (0, 5), (1, 4), (6, 9), (0, 3), (5, 8), (0, 2), (3, 6), (7, 9), (0, 1), (2, 4), (5, 7), (8, 9), (1, 2), (4, 6), (7, 8), (3, 5), (2, 5), (6, 8), (1, 3), (4, 7), (2, 3), (6, 7), (3, 4), (5, 6), (4, 5)],
+    // 11 elements, 35 comparators
+    &[(0, 9), (1, 6), (2, 4), (3, 7), (5, 8), (0, 1), (3, 5), (4, 10), (6, 9), (7, 8), (1, 3), (2, 5), (4, 7), (8, 10), (0, 4), (1, 2), (3, 7), (5, 9), (6, 8), (0, 1), (2, 6), (4, 5), (7, 8), (9, 10), (2, 4), (3, 6), (5, 7), (8, 9), (1, 2), (3, 4), (5, 6), (7, 8), (2, 3), (4, 5), (6, 7)],
+    // 12 elements, 39 comparators
+    &[(0, 8), (1, 7), (2, 6), (3, 11), (4, 10), (5, 9), (0, 1), (2, 5), (3, 4), (6, 9), (7, 8), (10, 11), (0, 2), (1, 6), (5, 10), (9, 11), (0, 3), (1, 2), (4, 6), (5, 7), (8, 11), (9, 10), (1, 4), (3, 5), (6, 8), (7, 10), (1, 3), (2, 5), (6, 9), (8, 10), (2, 3), (4, 5), (6, 7), (8, 9), (4, 6), (5, 7), (3, 4), (5, 6), (7, 8)],
+    // 13 elements, 45 comparators
+    &[(0, 12), (1, 10), (2, 9), (3, 7), (5, 11), (6, 8), (1, 6), (2, 3), (4, 11), (7, 9), (8, 10), (0, 4), (1, 2), (3, 6), (7, 8), (9, 10), (11, 12), (4, 6), (5, 9), (8, 11), (10, 12), (0, 5), (3, 8), (4, 7), (6, 11), (9, 10), (0, 1), (2, 5), (6, 9), (7, 8), (10, 11), (1, 3), (2, 4), (5, 6), (9, 10), (1, 2), (3, 4), (5, 7), (6, 8), (2, 3), (4, 5), (6, 7), (8, 9), (3, 4), (5, 6)],
+    // 14 elements, 51 comparators
+    &[(0, 13), (1, 12), (4, 8), (5, 6), (7, 11), (9, 10), (0, 5), (1, 7), (2, 9), (3, 4), (6, 13), (11, 12), (0, 1), (2, 3), (4, 5), (6, 8), (7, 9), (10, 11), (12, 13), (0, 2), (1, 3), (4, 10), (5, 11), (6, 7), (8, 9), (1, 2), (3, 12), (4, 6), (5, 7), (8, 10), (9, 11), (1, 4), (2, 6), (5, 8), (7, 10), (9, 13), (2, 4), (3, 6), (9, 12), (11, 13), (3, 5), (6, 8), (7, 9), (10, 12), (3, 4), (5, 6), (7, 8), (9, 10), (11, 12), (6, 7), (8, 9)],
+    // 15 elements, 56 comparators
+    &[(0, 13), (1, 12), (3, 14), (4, 8), (5, 6), (7, 11), (9, 10), (0, 5), (1, 7), (2, 9), (3, 4), (6, 13), (8, 14), (11, 12), (0, 1), (2, 3), (4, 5), (6, 8), (7, 9), (10, 11), (12, 13), (0, 2), (1, 3), (4, 10), (5, 11), (6, 7), (8, 9), (12, 14), (1, 2), (3, 12), (4, 6), (5, 7), (8, 10), (9, 11), (13, 14), (1, 4), (2, 6), (5, 8), (7, 10), (9, 13), (11, 14), (2, 4), (3, 6), (9, 12), (11, 13), (3, 5), (6, 8), (7, 9), (10, 12), (3, 4), (5, 6), (7, 8), (9, 10), (11, 12), (6, 7), (8, 9)],
+    // 16 elements, 60 comparators
+    &[(0, 13), (1, 12), (2, 15), (3, 14), (4, 8), (5, 6), (7, 11), (9, 10), (0, 5), (1, 7), (2, 9), (3, 4), (6, 13), (8, 14), (10, 15), (11, 12), (0, 1), (2, 3), (4, 5), (6, 8), (7, 9), (10, 11), (12, 13), (14, 15), (0, 2), (1, 3), (4, 10), (5, 11), (6, 7), (8, 9), (12, 14), (13, 15), (1, 2), (3, 12), (4, 6), (5, 7), (8, 10), (9, 11), (13, 14), (1, 4), (2, 6), (5, 8), (7, 10), (9, 13), (11, 14), (2, 4), (3, 6), (9, 12), (11, 13), (3, 5), (6, 8), (7, 9), (10, 12), (3, 4), (5, 6), (7, 8), (9, 10), (11, 12), (6, 7), (8, 9)],
+];
+
+pub(crate) fn network_sort<T, F>(v: &mut [T], is_less: &mut F)
+where
+    F: FnMut(&T, &T) -> bool,
+{
+    assert!(
+        v.len() <= MAX_NETWORK,
+        "no sorting network for {} elements",
+        v.len()
+    );
+    let network = NETWORKS[v.len()];
+    let v = v.as_mut_ptr();
+    for &(a, b) in network {
+        // SAFETY: a network for n elements only ever uses indices below n
+        unsafe { swap_if_less(v.add(a as usize), v.add(b as usize), is_less) };
+    }
+}
+
+// Puts the smaller of *a and *b at a. Both pointers are picked with a select rather than a
+// branch (cmov on x86), then copied in unconditionally: when nothing needs to move that's a copy
+// of each onto itself.
+//
+// SAFETY: a and b have to be valid, distinct, and point into the same slice
+unsafe fn swap_if_less<T, F>(a: *mut T, b: *mut T, is_less: &mut F)
+where
+    F: FnMut(&T, &T) -> bool,
+{
+    // only thing that can panic, and nothing has moved yet
+    let swap = is_less(&*b, &*a);
+    let from_a = if swap { b } else { a };
+    let from_b = if swap { a } else { b };
+    // if from_b is a, copying into a would overwrite it, so take it out first
+    let tmp = ManuallyDrop::new(ptr::read(from_b));
+    ptr::copy(from_a, a, 1);
+    ptr::copy_nonoverlapping(&*tmp, b, 1);
+}
+
+impl Sorter for NetworkSort {
+    fn sort_by<T, F>(&self, slice: &mut [T], mut compare: F)
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        network_sort(slice, &mut |a, b| compare(a, b).is_lt());
+    }
+}
+
+#[test]
+fn it_works() {
+    let mut things = vec![4, 2, 5, 3, 1, 6, 7, 8, 9];
+    NetworkSort.sort(&mut things);
+    assert_eq!(things, vec![1, 2, 3, 4, 5, 6, 7, 8, 9]);
+}
+
+#[test]
+fn every_network_sorts() {
+    // the 0-1 principle: a network that sorts every input of 0s and 1s sorts everything
+    for n in 0..=MAX_NETWORK {
+        for bits in 0..1u32 << n {
+            let mut v: Vec<u32> = (0..n).map(|i| bits >> i & 1).collect();
+            NetworkSort.sort(&mut v);
+            assert!(
+                v.windows(2).all(|w| w[0] <= w[1]),
+                "network {} on {:?}",
+                n,
+                v
+            );
+        }
+    }
+}
+
+#[test]
+fn cut_from_sixteen() {
+    for (n, &network) in NETWORKS.iter().enumerate().take(MAX_NETWORK).skip(14) {
+        let cut: Vec<(u8, u8)> = NETWORKS[MAX_NETWORK]
+            .iter()
+            .copied()
+            .filter(|&(_, b)| (b as usize) < n)
+            .collect();
+        assert_eq!(network, &cut[..], "network {}", n);
+    }
+}
@@ -0,0 +1,410 @@
+use super::Sorter;
+use crate::mergesort::{decrement_and_get, get_and_increment, MergeHole};
+use std::{cmp::Ordering, mem, ptr, slice};
+
+// TimSort: find the runs that are already sorted (real data usually has plenty), make short ones
+// longer with binary insertion sort, then merge neighbouring runs, keeping the stack of runs
+// balanced so merges stay about the same size. Merging gallops when one side keeps winning.
+pub struct TimSort;
+
+impl Sorter for TimSort {
+    fn sort_by<T, F>(&self, slice: &mut [T], mut compare: F)
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        timsort(slice, &mut |a, b| compare(a, b).is_lt());
+    }
+}
+
+// how many wins in a row before a merge switches to galloping, adapts as the merge goes
+const MIN_GALLOP: usize = 7;
+
+struct Run {
+    start: usize,
+    len: usize,
+}
+
+fn timsort<T, F>(v: &mut [T], is_less: &mut F)
+where
+    F: FnMut(&T, &T) -> bool,
+{
+    let len = v.len();
+    // zero sized values are all indistinguishable, any order is sorted
+    if mem::size_of::<T>() == 0 || len < 2 {
+        return;
+    }
+
+    let min_run = min_run_length(len);
+    // the shorter of two runs being merged is never more than half of everything
+    let mut buf = Vec::with_capacity(len / 2);
+    let mut runs = Vec::new();
+    let mut min_gallop = MIN_GALLOP;
+
+    let mut start = 0;
+    while start < len {
+        let mut run_len = count_run(&mut v[start..], is_less);
+        if run_len < min_run {
+            // too short to be worth merging by itself, pad it out
+            let end = (start + min_run).min(len);
+            binary_insertion_sort(&mut v[start..end], run_len, is_less);
+            run_len = end - start;
+        }
+        runs.push(Run {
+            start,
+            len: run_len,
+        });
+        start += run_len;
+
+        while let Some(i) = collapse(&runs) {
+            merge_at(v, &mut runs, i, buf.as_mut_ptr(), &mut min_gallop, is_less);
+        }
+    }
+
+    // everything is in runs now, merge whatever is left on the stack
+    while runs.len() > 1 {
+        let n = runs.len();
+        let i = if n >= 3 && runs[n - 3].len < runs[n - 1].len {
+            n - 3
+        } else {
+            n - 2
+        };
+        merge_at(v, &mut runs, i, buf.as_mut_ptr(), &mut min_gallop, is_less);
+    }
+}
+
+// between 32 and 64, chosen so len / min_run is a power of two or just under one,
+// which keeps the final merges balanced
+fn min_run_length(mut len: usize) -> usize {
+    // below 64 it's all one binary insertion sort
+    let mut r = 0;
+    while len >= 64 {
+        r |= len & 1;
+        len >>= 1;
+    }
+    len + r
+}
+
+// length of the sorted run at the start of v, descending runs get reversed so it's always ascending
+fn count_run<T, F>(v: &mut [T], is_less: &mut F) -> usize
+where
+    F: FnMut(&T, &T) -> bool,
+{
+    if v.len() < 2 {
+        return v.len();
+    }
+    let mut end = 2;
+    if is_less(&v[1], &v[0]) {
+        // has to be strictly descending, reversing equal elements would break stability
+        while end < v.len() && is_less(&v[end], &v[end - 1]) {
+            end += 1;
+        }
+        v[..end].reverse();
+    } else {
+        while end < v.len() && !is_less(&v[end], &v[end - 1]) {
+            end += 1;
+        }
+    }
+    end
+}
+
+// v[..sorted] is already sorted, insert the rest one by one
+fn binary_insertion_sort<T, F>(v: &mut [T], sorted: usize, is_less: &mut F)
+where
+    F: FnMut(&T, &T) -> bool,
+{
+    for i in sorted.max(1)..v.len() {
+        // after anything equal to it, that's what keeps it stable
+        let pos = v[..i].partition_point(|x| !is_less(&v[i], x));
+        v[pos..=i].rotate_right(1);
+    }
+}
+
+// the run stack invariants (with the fix from "OpenJDK's java.utils.Collection.sort() is broken"):
+// every run is bigger than the two above it put together, and bigger than the one above it.
+// returns which run to merge with the one after it, if anything is out of line
+fn collapse(runs: &[Run]) -> Option<usize> {
+    let n = runs.len();
+    if n >= 2
+        && ((n >= 3 && runs[n - 3].len <= runs[n - 2].len + runs[n - 1].len)
+            || (n >= 4 && runs[n - 4].len <= runs[n - 3].len + runs[n - 2].len))
+    {
+        if runs[n - 3].len < runs[n - 1].len {
+            Some(n - 3)
+        } else {
+            Some(n - 2)
+        }
+    } else if n >= 2 && runs[n - 2].len <= runs[n - 1].len {
+        Some(n - 2)
+    } else {
+        None
+    }
+}
+
+fn merge_at<T, F>(
+    v: &mut [T],
+    runs: &mut Vec<Run>,
+    i: usize,
+    buf: *mut T,
+    min_gallop: &mut usize,
+    is_less: &mut F,
+) where
+    F: FnMut(&T, &T) -> bool,
+{
+    let right = runs.remove(i + 1);
+    let left = &mut runs[i];
+    let v = &mut v[left.start..right.start + right.len];
+    // SAFETY: buf holds len / 2 of the whole slice, the shorter run never needs more
+    unsafe { merge(v, left.len, buf, min_gallop, is_less) };
+    left.len += right.len;
+}
+
+// Trims off the parts of both runs that are already where they belong, then merges what's left
+// with the shorter side copied out into buf (see mergesort::merge for how the hole works).
+//
+// SAFETY: buf has to be valid for writes of min(mid, v.len() - mid) elements and not overlap v,
+// and T must not be zero sized.
+unsafe fn merge<T, F>(v: &mut [T], mid: usize, buf: *mut T, min_gallop: &mut usize, is_less: &mut F)
+where
+    F: FnMut(&T, &T) -> bool,
+{
+    // the start of the left run that's <= the first of the right run is already in place
+    let skip = gallop_forward(&v[..mid], |x| !is_less(&v[mid], x));
+    let (v, mid) = (&mut v[skip..], mid - skip);
+    if mid == 0 {
+        return;
+    }
+    // and so is the end of the right run that's >= the last of the left run
+    let keep = gallop_backward(&v[mid..], |y| is_less(y, &v[mid - 1]));
+    let v = &mut v[..mid + keep];
+    if keep == 0 {
+        return;
+    }
+
+    if mid <= keep {
+        merge_lo(v, mid, buf, min_gallop, is_less);
+    } else {
+        merge_hi(v, mid, buf, min_gallop, is_less);
+    }
+}
+
+// left run is the shorter one: copy it out and merge forwards into the front of v
+unsafe fn merge_lo<T, F>(
+    v: &mut [T],
+    mid: usize,
+    buf: *mut T,
+    min_gallop: &mut usize,
+    is_less: &mut F,
+) where
+    F: FnMut(&T, &T) -> bool,
+{
+    let v = v.as_mut_ptr_range();
+    ptr::copy_nonoverlapping(v.start, buf, mid);
+    // buf[start..end] is what's left of the left run, dest is where the output goes next
+    let mut hole = MergeHole {
+        start: buf,
+        end: buf.add(mid),
+        dest: v.start,
+    };
+    let mut right = v.start.add(mid);
+
+    'merge: loop {
+        // one at a time, counting how many times in a row each side wins
+        let (mut left_wins, mut right_wins) = (0, 0);
+        while left_wins < *min_gallop && right_wins < *min_gallop {
+            if hole.start == hole.end || right == v.end {
+                break 'merge;
+            }
+            // only take from the right if it's strictly smaller, equal elements keep their order
+            if is_less(&*right, &*hole.start) {
+                ptr::copy_nonoverlapping(
+                    get_and_increment(&mut right),
+                    get_and_increment(&mut hole.dest),
+                    1,
+                );
+                right_wins += 1;
+                left_wins = 0;
+            } else {
+                ptr::copy_nonoverlapping(
+                    get_and_increment(&mut hole.start),
+                    get_and_increment(&mut hole.dest),
+                    1,
+                );
+                left_wins += 1;
+                right_wins = 0;
+            }
+        }
+
+        // one side keeps winning, so search for where its streak ends instead of comparing one by one
+        loop {
+            if hole.start == hole.end || right == v.end {
+                break 'merge;
+            }
+            let left = slice::from_raw_parts(hole.start, hole.end.offset_from(hole.start) as usize);
+            let n = gallop_forward(left, |x| !is_less(&*right, x));
+            ptr::copy_nonoverlapping(hole.start, hole.dest, n);
+            hole.start = hole.start.add(n);
+            hole.dest = hole.dest.add(n);
+            if hole.start == hole.end {
+                break 'merge;
+            }
+
+            let rest = slice::from_raw_parts(right, v.end.offset_from(right) as usize);
+            let m = gallop_forward(rest, |y| is_less(y, &*hole.start));
+            // the gap can be smaller than m, so these can overlap
+            ptr::copy(right, hole.dest, m);
+            right = right.add(m);
+            hole.dest = hole.dest.add(m);
+
+            if n < MIN_GALLOP && m < MIN_GALLOP {
+                // streaks are short again, back to one at a time, and make galloping harder to get into
+                *min_gallop += 1;
+                break;
+            }
+            // galloping is paying off, make it easier to get back into next time
+            *min_gallop = min_gallop.saturating_sub(1).max(1);
+        }
+    }
+    // whatever is left in buf fills the gap before `right` when hole drops
+}
+
+// right run is the shorter one: copy it out and merge backwards into the back of v
+unsafe fn merge_hi<T, F>(
+    v: &mut [T],
+    mid: usize,
+    buf: *mut T,
+    min_gallop: &mut usize,
+    is_less: &mut F,
+) where
+    F: FnMut(&T, &T) -> bool,
+{
+    let v = v.as_mut_ptr_range();
+    let right_len = v.end.offset_from(v.start) as usize - mid;
+    ptr::copy_nonoverlapping(v.start.add(mid), buf, right_len);
+    // buf[start..end] is what's left of the right run, v[..dest] is what's left of the left run
+    let mut hole = MergeHole {
+        start: buf,
+        end: buf.add(right_len),
+        dest: v.start.add(mid),
+    };
+    let mut out = v.end;
+
+    'merge: loop {
+        let (mut left_wins, mut right_wins) = (0, 0);
+        while left_wins < *min_gallop && right_wins < *min_gallop {
+            if hole.dest == v.start || hole.start == hole.end {
+                break 'merge;
+            }
+            // only take from the left if it's strictly bigger, equal elements keep their order
+            if is_less(&*hole.end.sub(1), &*hole.dest.sub(1)) {
+                ptr::copy_nonoverlapping(
+                    decrement_and_get(&mut hole.dest),
+                    decrement_and_get(&mut out),
+                    1,
+                );
+                left_wins += 1;
+                right_wins = 0;
+            } else {
+                ptr::copy_nonoverlapping(
+                    decrement_and_get(&mut hole.end),
+                    decrement_and_get(&mut out),
+                    1,
+                );
+                right_wins += 1;
+                left_wins = 0;
+            }
+        }
+
+        loop {
+            if hole.dest == v.start || hole.start == hole.end {
+                break 'merge;
+            }
+            let left = slice::from_raw_parts(v.start, hole.dest.offset_from(v.start) as usize);
+            // the end of the left run that's bigger than the last of the right run goes after it
+            let n = left.len() - gallop_backward(left, |x| !is_less(&*hole.end.sub(1), x));
+            out = out.sub(n);
+            hole.dest = hole.dest.sub(n);
+            // the gap can be smaller than n, so these can overlap
+            ptr::copy(hole.dest, out, n);
+            if hole.dest == v.start {
+                break 'merge;
+            }
+
+            let right =
+                slice::from_raw_parts(hole.start, hole.end.offset_from(hole.start) as usize);
+            // the end of the right run that's not smaller than the last of the left run goes after it
+            let m = right.len() - gallop_backward(right, |y| is_less(y, &*hole.dest.sub(1)));
+            out = out.sub(m);
+            hole.end = hole.end.sub(m);
+            ptr::copy_nonoverlapping(hole.end, out, m);
+
+            if n < MIN_GALLOP && m < MIN_GALLOP {
+                *min_gallop += 1;
+                break;
+            }
+            *min_gallop = min_gallop.saturating_sub(1).max(1);
+        }
+    }
+    // whatever is left in buf fills the gap after `dest` when hole drops
+}
+
+// `pred` holds for some prefix of `run` and not after it, find where it flips.
+// Gallops in from the start (1, 3, 7, 15, .. elements) until it overshoots, then binary searches
+// the last jump, so a flip k elements in costs O(log k) rather than O(log n)
+fn gallop_forward<T>(run: &[T], mut pred: impl FnMut(&T) -> bool) -> usize {
+    // run[..last] is known to hold
+    let mut last = 0;
+    let mut ofs = 1;
+    while ofs <= run.len() && pred(&run[ofs - 1]) {
+        last = ofs;
+        ofs = ofs * 2 + 1;
+    }
+    let end = (ofs - 1).min(run.len());
+    last + run[last..end].partition_point(pred)
+}
+
+// same, but galloping in from the end
+fn gallop_backward<T>(run: &[T], mut pred: impl FnMut(&T) -> bool) -> usize {
+    let n = run.len();
+    // run[first..] is known not to hold
+    let mut first = n;
+    let mut ofs = 1;
+    while ofs <= n && !pred(&run[n - ofs]) {
+        first = n - ofs;
+        ofs = ofs * 2 + 1;
+    }
+    let start = if ofs <= n { n - ofs + 1 } else { 0 };
+    start + run[start..first].partition_point(pred)
+}
+
+#[test]
+fn it_works() {
+    let mut things = vec![4, 2, 5, 3, 1, 6, 7, 8, 9];
+    TimSort.sort(&mut things);
+    assert_eq!(things, vec![1, 2, 3, 4, 5, 6, 7, 8, 9]);
+}
+
+#[test]
+fn runs_and_galloping() {
+    use rand::prelude::*;
+    let mut rand = rand::thread_rng();
+    // long sorted runs in both directions, with blocks of each other's values, so merges gallop
+    let mut things: Vec<u32> = (0..3000).collect();
+    things.extend((0..2000).rev().map(|i| i * 2));
+    things.extend((0..1000).map(|_| rand.gen_range(0..5000)));
+    let mut expected = things.clone();
+    expected.sort();
+    TimSort.sort(&mut things);
+    assert_eq!(things, expected);
+}
+
+#[test]
+fn stable() {
+    use rand::prelude::*;
+    let mut rand = rand::thread_rng();
+    // only the key is compared, the index says where each element started
+    let mut things: Vec<(u8, usize)> = (0..2000).map(|i| (rand.gen_range(0..10), i)).collect();
+    let mut expected = things.clone();
+    expected.sort_by_key(|&(k, _)| k);
+    timsort(&mut things, &mut |a, b| a.0 < b.0);
+    assert_eq!(things, expected);
+}